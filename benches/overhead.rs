@@ -0,0 +1,48 @@
+//! Rough per-probe overhead measurements.
+//!
+//! This uses a plain `main` rather than a benchmarking framework, so it still
+//! builds on the minimum supported Rust. Run with `cargo bench`, and compare
+//! the results against the empty loop baseline. Arguments for attached probes
+//! can only be measured with a tool attached, e.g. by running this under
+//! `stap` or `bpftrace` while it loops.
+
+use probe::{probe, probe_lazy};
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 100_000_000;
+
+fn measure(name: &str, mut f: impl FnMut(u32)) -> Duration {
+    // Warm up before timing anything.
+    for i in 0..ITERATIONS / 100 {
+        f(black_box(i));
+    }
+    let start = Instant::now();
+    for i in 0..ITERATIONS {
+        f(black_box(i));
+    }
+    let elapsed = start.elapsed();
+    let nanos = elapsed.as_secs_f64() * 1e9 / f64::from(ITERATIONS);
+    println!("{name:>16}: {nanos:.3} ns/iter");
+    elapsed
+}
+
+fn main() {
+    measure("empty", |i| {
+        black_box(i);
+    });
+    measure("probe", |i| {
+        black_box(i);
+        probe!(bench, nop);
+    });
+    measure("probe args", |i| {
+        probe!(bench, args, i, i + 1, i + 2);
+    });
+    measure("lazy", |i| {
+        black_box(i);
+        black_box(probe_lazy!(bench, lazy));
+    });
+    measure("lazy args", |i| {
+        black_box(probe_lazy!(bench, lazy_args, i, i + 1, i + 2));
+    });
+}
@@ -0,0 +1,20 @@
+use std::env;
+
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(probe_noop)");
+
+    // Sanitizers instrument memory accesses around the probe site, but they
+    // can't see into inline asm or the debugger's writes to semaphores, which
+    // shows up as false positives. Fall back to the no-op backend instead.
+    // The `noop` feature asks for the same, for instance in build tools.
+    if env::var_os("CARGO_CFG_SANITIZE").is_some() || env::var_os("CARGO_FEATURE_NOOP").is_some() {
+        println!("cargo:rustc-cfg=probe_noop");
+    }
+
+    // Named in the error when `require-native` rejects the no-op backend.
+    if let Some(target) = env::var_os("TARGET") {
+        println!("cargo:rustc-env=PROBE_TARGET={}", target.to_string_lossy());
+    }
+
+    println!("cargo:rerun-if-changed=build.rs");
+}
@@ -0,0 +1,68 @@
+//! Forwarding probes to Android ATrace.
+//!
+//! Android's systrace and Perfetto record app trace sections through ATrace.
+//! [`ATrace`] is a [`ProbeBackend`] that emits each probe firing as an empty
+//! section named `provider:name(args...)`, so the same `probe!` call sites
+//! show up as markers on the thread's track in the Perfetto UI, alongside the
+//! SDT notes for `simpleperf` and uprobes.
+//!
+//! Lazy probes only evaluate their arguments while ATrace is capturing, and
+//! nothing is formatted otherwise. This uses the NDK tracing functions from
+//! `libandroid`, which need API level 23.
+//!
+//! This module requires both the `backend` and `std` features, and is only
+//! available on Android.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use probe::atrace::ATrace;
+//! use probe::backend;
+//!
+//! static ATRACE: ATrace = ATrace;
+//!
+//! backend::set_backend(&ATRACE).unwrap();
+//! probe::probe!(foo, bar, 1, 2); // a "foo:bar(1, 2)" section
+//! ```
+
+use crate::backend::ProbeBackend;
+use core::ffi::c_char;
+use core::fmt::Write as _;
+use std::string::String;
+
+#[link(name = "android")]
+extern "C" {
+    fn ATrace_isEnabled() -> bool;
+    fn ATrace_beginSection(section_name: *const c_char);
+    fn ATrace_endSection();
+}
+
+/// A backend that emits probes as ATrace sections.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ATrace;
+
+impl ProbeBackend for ATrace {
+    fn enabled(&self, _provider: &'static str, _name: &'static str) -> bool {
+        unsafe { ATrace_isEnabled() }
+    }
+
+    fn fire(&self, provider: &'static str, name: &'static str, args: &[isize]) {
+        if !self.enabled(provider, name) {
+            return;
+        }
+        // Writing to a `String` can't fail.
+        let mut section = String::new();
+        let _ = write!(section, "{}:{}(", provider, name);
+        for (i, arg) in args.iter().enumerate() {
+            let sep = if i == 0 { "" } else { ", " };
+            let _ = write!(section, "{}{}", sep, arg);
+        }
+        section.push_str(")\0");
+        // SAFETY: the name is NUL-terminated, and probe names can't contain
+        // another NUL.
+        unsafe {
+            ATrace_beginSection(section.as_ptr().cast());
+            ATrace_endSection();
+        }
+    }
+}
@@ -0,0 +1,121 @@
+//! In-process probe backends
+//!
+//! With the `backend` feature, an application can install one additional
+//! [`ProbeBackend`] that sees every probe firing in the process, alongside
+//! the platform's own implementation. This can be used to tee events into a
+//! custom telemetry pipeline, or to test that probes fire as expected.
+//!
+//! Each probe checks for an installed backend with an atomic load before
+//! anything else, and lazy probes also ask the backend whether it wants that
+//! probe at all before evaluating their arguments. Whole providers can also be
+//! muted at runtime through the [`control`](crate::control) module, and with
+//! the `std` feature, `throttle::Throttle` limits how often each probe reaches
+//! a backend. On Linux, `user_events::UserEvents` forwards probes to the
+//! kernel's `user_events`, and on Android, `atrace::ATrace` shows them in
+//! systrace and Perfetto. Probes are limited to 12 arguments with this
+//! feature, the same as `<sys/sdt.h>`.
+//!
+//! # Example
+//!
+//! ```
+//! use probe::backend::{self, ProbeBackend};
+//! use probe::probe;
+//!
+//! struct Printer;
+//!
+//! impl ProbeBackend for Printer {
+//!     fn fire(&self, provider: &'static str, name: &'static str, args: &[isize]) {
+//!         println!("{}:{} {:?}", provider, name, args);
+//!     }
+//! }
+//!
+//! static PRINTER: Printer = Printer;
+//!
+//! backend::set_backend(&PRINTER).unwrap();
+//! probe!(foo, bar, 1, 2, 3); // prints "foo:bar [1, 2, 3]"
+//! ```
+
+use crate::control::Site;
+use core::fmt;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+/// An in-process consumer of probe firings.
+pub trait ProbeBackend: Sync {
+    /// Returns `true` if this backend wants to see the given probe.
+    ///
+    /// This is checked by lazy probes before their arguments are evaluated.
+    /// Eager probes evaluate their arguments regardless, and call
+    /// [`fire`](Self::fire) directly.
+    fn enabled(&self, provider: &'static str, name: &'static str) -> bool {
+        let _ = (provider, name);
+        true
+    }
+
+    /// Called for every probe firing, with its arguments cast `as isize`.
+    fn fire(&self, provider: &'static str, name: &'static str, args: &[isize]);
+}
+
+/// The error returned if a backend has already been installed.
+#[derive(Debug)]
+pub struct SetBackendError(());
+
+impl fmt::Display for SetBackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("a probe backend has already been installed")
+    }
+}
+
+const UNINITIALIZED: usize = 0;
+const INITIALIZING: usize = 1;
+const INITIALIZED: usize = 2;
+
+static STATE: AtomicUsize = AtomicUsize::new(UNINITIALIZED);
+static mut BACKEND: Option<&'static dyn ProbeBackend> = None;
+
+/// Install the process-wide probe backend.
+///
+/// This may only be called once, and fails if a backend is already installed.
+pub fn set_backend(backend: &'static dyn ProbeBackend) -> Result<(), SetBackendError> {
+    match STATE.compare_exchange(
+        UNINITIALIZED,
+        INITIALIZING,
+        Ordering::Acquire,
+        Ordering::Relaxed,
+    ) {
+        Ok(_) => {
+            unsafe { BACKEND = Some(backend) };
+            STATE.store(INITIALIZED, Ordering::Release);
+            Ok(())
+        }
+        Err(_) => Err(SetBackendError(())),
+    }
+}
+
+/// Returns the installed backend, if any.
+#[inline]
+pub fn backend() -> Option<&'static dyn ProbeBackend> {
+    if STATE.load(Ordering::Acquire) == INITIALIZED {
+        unsafe { BACKEND }
+    } else {
+        None
+    }
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn enabled(site: &Site) -> bool {
+    match backend() {
+        Some(backend) => site.enabled() && backend.enabled(site.provider, site.name),
+        None => false,
+    }
+}
+
+#[doc(hidden)]
+#[inline]
+pub fn fire(site: &Site, args: &[isize]) {
+    if let Some(backend) = backend() {
+        if site.enabled() {
+            backend.fire(site.provider, site.name, args);
+        }
+    }
+}
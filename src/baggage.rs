@@ -0,0 +1,65 @@
+//! A per-thread correlation value that probes can carry.
+//!
+//! Applications can stash an opaque `u64` here, like a tenant or request id,
+//! and probes marked `#[baggage]` pass it as an extra trailing argument. That
+//! lets tracers correlate events across a request without threading the id
+//! through every probe call. The value is `0` when nothing has been set.
+//!
+//! The slot belongs to the current thread, so async tasks that move between
+//! threads should set it around each poll, for example with [`scope`].
+//!
+//! This module requires the `std` feature.
+//!
+//! # Example
+//!
+//! ```
+//! use probe::{baggage, probe};
+//!
+//! fn handle(request_id: u64) {
+//!     baggage::scope(request_id, || {
+//!         // Fires with `42, request_id`.
+//!         probe!(#[baggage] foo, handle, 42);
+//!     });
+//! }
+//! # handle(7);
+//! ```
+
+use std::cell::Cell;
+
+std::thread_local! {
+    static BAGGAGE: Cell<u64> = const { Cell::new(0) };
+}
+
+/// Returns the current thread's baggage, or `0` if none is set.
+///
+/// This never allocates, and returns `0` during thread teardown, so probes in
+/// allocators and thread-local destructors can still use it.
+#[inline]
+pub fn get() -> u64 {
+    BAGGAGE.try_with(Cell::get).unwrap_or(0)
+}
+
+/// Set the current thread's baggage, returning the previous value.
+///
+/// During thread teardown, this does nothing and returns `0`.
+#[inline]
+pub fn set(value: u64) -> u64 {
+    BAGGAGE
+        .try_with(|baggage| baggage.replace(value))
+        .unwrap_or(0)
+}
+
+/// Call `f` with the current thread's baggage set to `value`, and restore the
+/// previous value afterward, even if `f` panics.
+pub fn scope<R>(value: u64, f: impl FnOnce() -> R) -> R {
+    struct Restore(u64);
+
+    impl Drop for Restore {
+        fn drop(&mut self) {
+            set(self.0);
+        }
+    }
+
+    let _restore = Restore(set(value));
+    f()
+}
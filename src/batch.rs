@@ -0,0 +1,73 @@
+//! Coalescing many events into one probe firing.
+//!
+//! Each probe that fires while a tracer is attached costs a trap into the
+//! kernel, which dominates in code that produces events at a very high rate.
+//! A [`Batch`] instead collects up to `N` values in memory, and
+//! [`probe_batch!`](crate::probe_batch) fires a single probe each time it
+//! fills, with a pointer to the packed `isize` values and their count. The
+//! tracer copies the array out of the process in one read, trading the exact
+//! timing of each event for much lower overhead.
+//!
+//! The batch is plain memory owned by the caller, with no synchronization, so
+//! each thread should have its own, for instance in a `thread_local!`.
+
+/// A fixed-capacity buffer of probe values.
+#[derive(Clone, Debug)]
+pub struct Batch<const N: usize> {
+    values: [isize; N],
+    len: usize,
+}
+
+impl<const N: usize> Batch<N> {
+    /// Create an empty batch.
+    #[inline]
+    pub const fn new() -> Self {
+        Batch {
+            values: [0; N],
+            len: 0,
+        }
+    }
+
+    /// Add a value, returning `true` if the batch is now full.
+    ///
+    /// If the batch was already full, the value is dropped.
+    #[inline]
+    pub fn push(&mut self, value: isize) -> bool {
+        if self.len < N {
+            self.values[self.len] = value;
+            self.len += 1;
+        }
+        self.len == N
+    }
+
+    /// Returns the values collected so far.
+    #[inline]
+    pub fn as_slice(&self) -> &[isize] {
+        &self.values[..self.len]
+    }
+
+    /// Returns the number of values collected so far.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if no values have been collected.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Discard all collected values.
+    #[inline]
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+}
+
+impl<const N: usize> Default for Batch<N> {
+    #[inline]
+    fn default() -> Self {
+        Batch::new()
+    }
+}
@@ -0,0 +1,123 @@
+//! Measuring what probes cost on this machine.
+//!
+//! Leaving probes in a hot loop is easier to justify with numbers in hand.
+//! [`baseline`] times an empty loop against the same loop with a probe in it,
+//! so the difference is the overhead of each kind of probe, as compiled for
+//! this target. The probes it times are in the reserved `rust_bench` provider:
+//!
+//! | probe   | arguments          | measures                          |
+//! |---------|--------------------|-----------------------------------|
+//! | `nop`   |                    | [`Baseline::nop`]                 |
+//! | `lazy`  | `i`                | [`Baseline::semaphore`]           |
+//! | `args`  | `i`, `i+1`, `i+2`  | [`Baseline::args`]                |
+//!
+//! With no tool attached, these are the costs every build pays. Attach a tool
+//! to `rust_bench:*` while measuring to see the cost of enabled probes,
+//! including the trap into the tracer. For finer comparisons of your own
+//! probes, see the `overhead` benchmark in the repository.
+//!
+//! This module requires the `std` feature.
+//!
+//! # Example
+//!
+//! ```
+//! let baseline = probe::bench::measure(1000);
+//! println!("{}", baseline);
+//! ```
+
+use crate::support::{Platform, PLATFORM};
+use crate::{probe, probe_lazy};
+use std::fmt;
+use std::hint::black_box;
+use std::time::Instant;
+
+/// The name of the provider for these probes, in the reserved `rust_*`
+/// namespace.
+pub const PROVIDER: &str = "rust_bench";
+
+/// The number of iterations [`baseline`] times for each measurement.
+pub const ITERATIONS: u32 = 10_000_000;
+
+/// The time per iteration of a loop with and without each kind of probe.
+#[derive(Clone, Copy, Debug)]
+#[non_exhaustive]
+pub struct Baseline {
+    /// The architecture this was measured on, like `x86_64`.
+    pub arch: &'static str,
+    /// The probe implementation that was compiled in.
+    pub platform: Platform,
+    /// The number of iterations timed for each measurement.
+    pub iterations: u32,
+    /// Nanoseconds per iteration of the loop alone.
+    pub empty: f64,
+    /// Nanoseconds per iteration with an eager probe without arguments,
+    /// which is a single NOP where probes are supported.
+    pub nop: f64,
+    /// Nanoseconds per iteration with a lazy probe, which only checks its
+    /// semaphore unless a tool is attached.
+    pub semaphore: f64,
+    /// Nanoseconds per iteration with an eager probe of three arguments.
+    pub args: f64,
+}
+
+impl fmt::Display for Baseline {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{} ({:?}):", self.arch, self.platform)?;
+        writeln!(f, "  empty      {:.3} ns/iter", self.empty)?;
+        writeln!(f, "  nop        {:+.3} ns/iter", self.nop - self.empty)?;
+        writeln!(
+            f,
+            "  semaphore  {:+.3} ns/iter",
+            self.semaphore - self.empty
+        )?;
+        write!(f, "  args       {:+.3} ns/iter", self.args - self.empty)
+    }
+}
+
+/// Measure the overhead of probes with [`ITERATIONS`] per measurement.
+///
+/// This takes a fraction of a second in an optimized build, and much longer
+/// in a debug build, whose numbers mean little anyway.
+#[inline]
+pub fn baseline() -> Baseline {
+    measure(ITERATIONS)
+}
+
+/// Measure the overhead of probes with `iterations` per measurement.
+///
+/// Each measurement first runs a hundredth as many iterations to warm up.
+// Inlined, like the other probes of this crate, so that only binaries that
+// measure carry the notes of `rust_bench`.
+#[inline]
+pub fn measure(iterations: u32) -> Baseline {
+    Baseline {
+        arch: std::env::consts::ARCH,
+        platform: PLATFORM,
+        iterations,
+        empty: time(iterations, |i| {
+            black_box(i);
+        }),
+        nop: time(iterations, |i| {
+            black_box(i);
+            probe!(rust_bench, nop);
+        }),
+        semaphore: time(iterations, |i| {
+            black_box(probe_lazy!(rust_bench, lazy, i));
+        }),
+        args: time(iterations, |i| {
+            probe!(rust_bench, args, i, i.wrapping_add(1), i.wrapping_add(2));
+        }),
+    }
+}
+
+fn time(iterations: u32, mut f: impl FnMut(u32)) -> f64 {
+    for i in 0..iterations / 100 {
+        f(black_box(i));
+    }
+    let start = Instant::now();
+    for i in 0..iterations {
+        f(black_box(i));
+    }
+    let nanos = start.elapsed().as_secs_f64() * 1e9;
+    nanos / f64::from(iterations.max(1))
+}
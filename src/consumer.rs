@@ -0,0 +1,96 @@
+//! Enabling probes in other processes.
+//!
+//! Tools like `perf` and `bpftrace` can attach to a probe's location without
+//! knowing about its semaphore, and then lazy probes never fire. This module
+//! lets a bespoke tracer manage the semaphores itself, the way SystemTap and
+//! GDB do, by writing to the target's memory through `/proc/<pid>/mem`.
+//!
+//! This needs the same permission as `ptrace`, and like those tools, the
+//! counter is updated with a plain read and write. The target's own threads
+//! never write semaphores, but two tools updating the same one at exactly the
+//! same time may lose a count.
+//!
+//! This module requires the `std` feature, and is only available on Linux.
+
+use crate::registry::{self, Error};
+use std::format;
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::FileExt;
+use std::vec::Vec;
+
+/// A probe enabled in another process, which is disabled again on drop.
+#[derive(Debug)]
+pub struct Enabled {
+    mem: File,
+    semaphores: Vec<u64>,
+}
+
+/// Enable the named probe in process `pid`, by incrementing its semaphores.
+///
+/// The semaphores of every lazy site of the probe are incremented once each,
+/// even when several sites share one after inlining. They are decremented
+/// again when the returned guard is dropped.
+///
+/// # Example
+///
+/// ```
+/// # use probe::probe_lazy;
+/// # if probe::is_supported() {
+/// let enabled = probe::consumer::enable_in(std::process::id(), "foo", "bar").unwrap();
+/// assert!(probe_lazy!(foo, bar));
+/// drop(enabled);
+/// assert!(!probe_lazy!(foo, bar));
+/// # }
+/// ```
+pub fn enable_in(pid: u32, provider: &str, name: &str) -> Result<Enabled, Error> {
+    let registry = registry::process(pid)?;
+    let mut semaphores: Vec<u64> = registry
+        .find(provider, name)
+        .iter()
+        .filter_map(|probe| probe.semaphore)
+        .collect();
+    semaphores.sort_unstable();
+    semaphores.dedup();
+    if semaphores.is_empty() {
+        return Err(Error::NoSemaphore(format!("{}:{}", provider, name)));
+    }
+
+    let mem = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(format!("/proc/{}/mem", pid))?;
+    let mut enabled = Enabled {
+        mem,
+        semaphores: Vec::with_capacity(semaphores.len()),
+    };
+    for semaphore in semaphores {
+        // If this fails, dropping `enabled` restores what was done so far.
+        enabled.update(semaphore, 1)?;
+        enabled.semaphores.push(semaphore);
+    }
+    Ok(enabled)
+}
+
+impl Enabled {
+    /// Returns the runtime addresses of the semaphores that were incremented.
+    pub fn semaphores(&self) -> &[u64] {
+        &self.semaphores
+    }
+
+    fn update(&self, address: u64, delta: i16) -> Result<(), Error> {
+        let mut buf = [0; 2];
+        self.mem.read_exact_at(&mut buf, address)?;
+        let count = u16::from_ne_bytes(buf).wrapping_add(delta as u16);
+        self.mem.write_all_at(&count.to_ne_bytes(), address)?;
+        Ok(())
+    }
+}
+
+impl Drop for Enabled {
+    fn drop(&mut self) {
+        for &semaphore in &self.semaphores {
+            // The process may have exited, which leaves nothing to restore.
+            let _ = self.update(semaphore, -1);
+        }
+    }
+}
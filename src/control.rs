@@ -0,0 +1,337 @@
+//! Runtime control of in-process backends
+//!
+//! These switches only affect the in-process [`ProbeBackend`], not external
+//! tools, which attach and detach probes on their own. A muted provider's
+//! probes are not passed to the backend, and its lazy probes don't evaluate
+//! their arguments unless a tool is attached.
+//!
+//! Probes can be switched by whole provider, or by glob patterns over
+//! `provider:name`, where `*` matches any run of characters and `?` matches
+//! any one. For example, `db:*` covers every probe of the `db` provider, and
+//! `*:error*` covers every probe whose name starts with `error`.
+//!
+//! Each probe site caches whether it's enabled, and only rechecks the muted
+//! providers and patterns after the settings have changed, so muting doesn't
+//! add any work to probes that fire afterward. A site never waits for the
+//! settings, so probes can fire from a signal handler that interrupted a
+//! change: if another thread holds the settings at that moment, the site
+//! keeps its previous state, and checks again the next time it fires.
+//!
+//! This module requires the `backend` feature.
+//!
+//! [`ProbeBackend`]: crate::backend::ProbeBackend
+
+use core::cell::UnsafeCell;
+use core::fmt;
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// The maximum number of providers that can be muted at once.
+pub const MAX_MUTED: usize = 32;
+
+/// The maximum number of patterns that can be set at once.
+pub const MAX_PATTERNS: usize = 16;
+
+/// The longest provider name or pattern that can be set, in bytes.
+pub const MAX_LEN: usize = 64;
+
+// Bumped on every change, to invalidate the cache in each probe `Site`.
+// This starts at one, so a fresh site never matches.
+static GENERATION: AtomicUsize = AtomicUsize::new(1);
+
+// A copy of a provider name or pattern, so this doesn't need to allocate or
+// keep a reference to the caller's string.
+#[derive(Clone, Copy)]
+struct Name {
+    len: usize,
+    bytes: [u8; MAX_LEN],
+}
+
+impl Name {
+    const EMPTY: Name = Name {
+        len: 0,
+        bytes: [0; MAX_LEN],
+    };
+
+    fn new(s: &str) -> Result<Name, ControlError> {
+        if s.len() > MAX_LEN {
+            return Err(ControlError("bytes in a name", MAX_LEN));
+        }
+        let mut name = Name::EMPTY;
+        name.bytes[..s.len()].copy_from_slice(s.as_bytes());
+        name.len = s.len();
+        Ok(name)
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+struct Settings {
+    muted: [Name; MAX_MUTED],
+    muted_len: usize,
+    // Patterns are kept in the order they were set, so the last match wins.
+    patterns: [(Name, bool); MAX_PATTERNS],
+    patterns_len: usize,
+}
+
+impl Settings {
+    fn muted(&self) -> &[Name] {
+        &self.muted[..self.muted_len]
+    }
+
+    fn enabled(&self, provider: &str, name: &str) -> bool {
+        let patterns = &self.patterns[..self.patterns_len];
+        let matched = patterns
+            .iter()
+            .rev()
+            .find(|p| matches(p.0.as_bytes(), provider, name));
+        matched.map_or_else(|| self.provider_enabled(provider), |p| p.1)
+    }
+
+    fn provider_enabled(&self, provider: &str) -> bool {
+        !self
+            .muted()
+            .iter()
+            .any(|m| m.as_bytes() == provider.as_bytes())
+    }
+}
+
+// The settings are only read when a site refreshes its cache, so a simple
+// spin lock will do. Sites only try to take it, and never spin.
+struct Locked {
+    lock: AtomicBool,
+    settings: UnsafeCell<Settings>,
+}
+
+// SAFETY: the settings are only accessed while holding the lock.
+unsafe impl Sync for Locked {}
+
+static SETTINGS: Locked = Locked {
+    lock: AtomicBool::new(false),
+    settings: UnsafeCell::new(Settings {
+        muted: [Name::EMPTY; MAX_MUTED],
+        muted_len: 0,
+        patterns: [(Name::EMPTY, false); MAX_PATTERNS],
+        patterns_len: 0,
+    }),
+};
+
+impl Locked {
+    fn with<R>(&self, f: impl FnOnce(&mut Settings) -> R) -> R {
+        while self
+            .lock
+            .compare_exchange_weak(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            core::hint::spin_loop();
+        }
+        // SAFETY: we hold the lock, and `f` can't reenter it.
+        let result = f(unsafe { &mut *self.settings.get() });
+        self.lock.store(false, Ordering::Release);
+        result
+    }
+
+    fn try_with<R>(&self, f: impl FnOnce(&mut Settings) -> R) -> Option<R> {
+        self.lock
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()?;
+        // SAFETY: we hold the lock, and `f` can't reenter it.
+        let result = f(unsafe { &mut *self.settings.get() });
+        self.lock.store(false, Ordering::Release);
+        Some(result)
+    }
+}
+
+/// The error returned when too many providers are muted, or too many patterns
+/// are set, at once, or when a name is too long.
+#[derive(Debug)]
+pub struct ControlError(&'static str, usize);
+
+impl fmt::Display for ControlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "no more than {} {}", self.1, self.0)
+    }
+}
+
+/// Enable or disable all probes of `provider` for the in-process backend.
+///
+/// Providers are enabled by default. This fails if more than [`MAX_MUTED`]
+/// providers would be disabled at once, or if a disabled provider's name is
+/// longer than [`MAX_LEN`].
+///
+/// # Example
+///
+/// ```
+/// probe::control::set_provider_enabled("db", false).unwrap();
+/// assert!(!probe::control::provider_enabled("db"));
+/// ```
+pub fn set_provider_enabled(provider: &str, enabled: bool) -> Result<(), ControlError> {
+    SETTINGS.with(|settings| {
+        let muted = settings.muted();
+        let i = muted
+            .iter()
+            .position(|m| m.as_bytes() == provider.as_bytes());
+        match (i, enabled) {
+            (Some(i), true) => {
+                settings.muted[i..settings.muted_len].rotate_left(1);
+                settings.muted_len -= 1;
+            }
+            (None, false) => {
+                let name = Name::new(provider)?;
+                if settings.muted_len == MAX_MUTED {
+                    return Err(ControlError("providers can be muted", MAX_MUTED));
+                }
+                settings.muted[settings.muted_len] = name;
+                settings.muted_len += 1;
+            }
+            _ => {}
+        }
+        Ok(())
+    })?;
+    GENERATION.fetch_add(1, Ordering::Release);
+    Ok(())
+}
+
+/// Returns `true` unless `provider` has been disabled.
+pub fn provider_enabled(provider: &str) -> bool {
+    SETTINGS.with(|settings| settings.provider_enabled(provider))
+}
+
+/// Enable or disable all probes matching a `provider:name` glob pattern.
+///
+/// Patterns take precedence over [`set_provider_enabled`], and when several
+/// patterns match a probe, the one set most recently wins. Setting a pattern
+/// again moves it to the end with the new state. This fails if more than
+/// [`MAX_PATTERNS`] patterns would be set at once, or if the pattern is longer
+/// than [`MAX_LEN`]. Use [`clear_patterns`] to remove them all.
+///
+/// # Example
+///
+/// ```
+/// use probe::control;
+///
+/// control::set_pattern_enabled("db:*", false).unwrap();
+/// control::set_pattern_enabled("*:error*", true).unwrap();
+/// assert!(!control::enabled("db", "query"));
+/// assert!(control::enabled("db", "error_timeout"));
+/// # control::clear_patterns();
+/// ```
+pub fn set_pattern_enabled(pattern: &str, enabled: bool) -> Result<(), ControlError> {
+    let pattern = Name::new(pattern)?;
+    SETTINGS.with(|settings| {
+        let (list, len) = (&mut settings.patterns, &mut settings.patterns_len);
+        let bytes = pattern.as_bytes();
+        if let Some(i) = list[..*len].iter().position(|p| p.0.as_bytes() == bytes) {
+            list[i..*len].rotate_left(1);
+            *len -= 1;
+        }
+        if *len == MAX_PATTERNS {
+            return Err(ControlError("patterns can be set", MAX_PATTERNS));
+        }
+        list[*len] = (pattern, enabled);
+        *len += 1;
+        Ok(())
+    })?;
+    GENERATION.fetch_add(1, Ordering::Release);
+    Ok(())
+}
+
+/// Remove every pattern set by [`set_pattern_enabled`].
+pub fn clear_patterns() {
+    SETTINGS.with(|settings| settings.patterns_len = 0);
+    GENERATION.fetch_add(1, Ordering::Release);
+}
+
+/// Returns whether the probe `provider:name` is enabled, considering both the
+/// patterns and the muted providers.
+pub fn enabled(provider: &str, name: &str) -> bool {
+    SETTINGS.with(|settings| settings.enabled(provider, name))
+}
+
+/// Match `provider:name` against a pattern, where a pattern without a colon
+/// applies to the provider alone.
+fn matches(pattern: &[u8], provider: &str, name: &str) -> bool {
+    match pattern.iter().position(|&b| b == b':') {
+        Some(colon) => {
+            glob(&pattern[..colon], provider.as_bytes())
+                && glob(&pattern[colon + 1..], name.as_bytes())
+        }
+        None => glob(pattern, provider.as_bytes()),
+    }
+}
+
+/// A glob match with `*` and `?`, backtracking only to the last `*`.
+fn glob(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    let mut star = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                star = Some((p, t));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match star {
+                Some((sp, st)) => {
+                    p = sp + 1;
+                    t = st + 1;
+                    star = Some((sp, st + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// The cached state of a single probe site.
+#[doc(hidden)]
+pub struct Site {
+    pub provider: &'static str,
+    pub name: &'static str,
+    // The generation when this was last checked, shifted left by one, with
+    // the low bit set if the site was enabled.
+    state: AtomicUsize,
+}
+
+impl Site {
+    pub const fn new(provider: &'static str, name: &'static str) -> Site {
+        Site {
+            provider,
+            name,
+            state: AtomicUsize::new(0),
+        }
+    }
+
+    #[inline]
+    pub fn enabled(&self) -> bool {
+        let generation = GENERATION.load(Ordering::Acquire);
+        let state = self.state.load(Ordering::Relaxed);
+        if state >> 1 == generation & (usize::MAX >> 1) {
+            return state & 1 != 0;
+        }
+        self.refresh(generation)
+    }
+
+    #[cold]
+    fn refresh(&self, generation: usize) -> bool {
+        let (provider, name) = (self.provider, self.name);
+        match SETTINGS.try_with(|settings| settings.enabled(provider, name)) {
+            Some(enabled) => {
+                self.state
+                    .store(generation << 1 | enabled as usize, Ordering::Relaxed);
+                enabled
+            }
+            // The settings are changing, maybe on this very thread, so keep
+            // the previous state, or the default for a site never checked.
+            None => {
+                let state = self.state.load(Ordering::Relaxed);
+                state >> 1 == 0 || state & 1 != 0
+            }
+        }
+    }
+}
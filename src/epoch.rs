@@ -0,0 +1,44 @@
+//! Probes for epoch-based memory reclamation.
+//!
+//! Concurrent data structures using epoch-based reclamation, in the style of
+//! `crossbeam-epoch`, free memory in batches whenever the global epoch can
+//! advance. Those batches can cause latency spikes far from the code that
+//! retired the memory, so these helpers fire probes in the `rust_epoch`
+//! provider to make them visible to external tracers:
+//!
+//! | probe          | arguments         | fired by    |
+//! |----------------|-------------------|-------------|
+//! | `advance`      | `epoch`           | [`advance`] |
+//! | `flush_begin`  | `epoch`, `count`  | [`flush`]   |
+//! | `flush_end`    | `epoch`, `count`  | [`flush`]   |
+//!
+//! This module requires the `epoch` feature.
+
+use crate::probe;
+
+/// The name of the provider for these probes, in the reserved `rust_*`
+/// namespace.
+pub const PROVIDER: &str = "rust_epoch";
+
+/// Fire `rust_epoch:advance` when the global epoch advances to `epoch`.
+#[inline]
+pub fn advance(epoch: usize) {
+    probe!(rust_epoch, advance, epoch);
+}
+
+/// Call `f` to free `count` deferred objects retired before `epoch`, between
+/// `rust_epoch:flush_begin` and `rust_epoch:flush_end` probes.
+///
+/// # Example
+///
+/// ```
+/// let mut garbage = vec![Box::new(1), Box::new(2)];
+/// probe::epoch::flush(3, garbage.len(), || garbage.clear());
+/// ```
+#[inline]
+pub fn flush<R>(epoch: usize, count: usize, f: impl FnOnce() -> R) -> R {
+    probe!(rust_epoch, flush_begin, epoch, count);
+    let result = f();
+    probe!(rust_epoch, flush_end, epoch, count);
+    result
+}
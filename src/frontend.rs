@@ -0,0 +1,82 @@
+//! Dispatch from the public macros to the platform and in-process backends.
+
+// This crate's version, as a literal for the asm template of the version note,
+// where `env!("CARGO_PKG_VERSION")` would see the calling crate instead. The
+// verify tests check that it matches Cargo.toml.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! probe_crate_version(
+    () => ("0.5.1");
+);
+
+// The barrier on each side of an `#[ordered]` probe, with or without a fence.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! probe_fence(
+    () => (::core::sync::atomic::compiler_fence(::core::sync::atomic::Ordering::SeqCst));
+    (fence) => (::core::sync::atomic::fence(::core::sync::atomic::Ordering::SeqCst));
+);
+
+#[cfg(not(feature = "backend"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! backend_probe(
+    ($(#[$attr:ident])? $provider:ident, $name:ident, $($arg:expr,)*)
+    => ($crate::platform_probe!($(#[$attr])? $provider, $name, $($arg,)*));
+);
+
+#[cfg(not(feature = "backend"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! backend_probe_lazy(
+    ($(#[export_name = $export:literal])? $provider:ident, $name:ident, $($arg:expr,)*)
+    => ($crate::platform_probe_lazy!($(#[export_name = $export])? $provider, $name, $($arg,)*));
+);
+
+// Each argument is bound once to one of these names, in order, so both the
+// platform probe and the backend see the same values.
+#[cfg(feature = "backend")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! backend_probe(
+    ($(#[$attr:ident])? $provider:ident, $name:ident, $($arg:expr,)*)
+    => ($crate::backend_probe!(@bind [$(#[$attr])?] $provider, $name, [$($arg,)*] []
+            [arg1 arg2 arg3 arg4 arg5 arg6 arg7 arg8 arg9 arg10 arg11 arg12]));
+
+    (@bind [$($attr:tt)*] $provider:ident, $name:ident, [$arg:expr, $($rest:expr,)*]
+        [$($bound:ident)*] [$next:ident $($names:ident)*]
+    ) => (match ($arg) as isize {
+        $next => $crate::backend_probe!(@bind [$($attr)*] $provider, $name, [$($rest,)*]
+            [$($bound)* $next] [$($names)*]),
+    });
+
+    (@bind [$($attr:tt)*] $provider:ident, $name:ident, [] [$($bound:ident)*]
+        [$($names:ident)*]
+    ) => ({
+        $crate::platform_probe!($($attr)* $provider, $name, $($bound,)*);
+        static SITE: $crate::control::Site =
+            $crate::control::Site::new(::core::stringify!($provider), ::core::stringify!($name));
+        $crate::backend::fire(&SITE, &[$($bound),*]);
+    });
+);
+
+// When the backend wants this probe, this fires an eager probe site instead of
+// the lazy one, which is still a probe with the same name for external tools.
+#[cfg(feature = "backend")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! backend_probe_lazy(
+    ($(#[export_name = $export:literal])? $provider:ident, $name:ident, $($arg:expr,)*) => ({
+        let enabled = {
+            static SITE: $crate::control::Site =
+                $crate::control::Site::new(::core::stringify!($provider), ::core::stringify!($name));
+            $crate::backend::enabled(&SITE)
+        };
+        if enabled {
+            $crate::backend_probe!($provider, $name, $($arg,)*);
+            true
+        } else {
+            $crate::platform_probe_lazy!($(#[export_name = $export])? $provider, $name, $($arg,)*)
+        }
+    });
+);
@@ -0,0 +1,208 @@
+//! Guards that fire probes when they go out of scope.
+//!
+//! Each guard carries an outcome code, which defaults to `0` and may be set
+//! before the guard is dropped, so the exit probe can distinguish success from
+//! errors. The `Timer` guard needs a clock, so it's only available with the
+//! `std` feature.
+//!
+//! A `Timer` held across an `.await` measures wall time, including the time
+//! its task spent waiting to be woken. `TimedFuture` instead wraps a future
+//! and also measures only the time spent polling it, and can fire probes each
+//! time the future is suspended and resumed.
+
+#[cfg(feature = "std")]
+use core::future::Future;
+#[cfg(feature = "std")]
+use core::pin::Pin;
+#[cfg(feature = "std")]
+use core::task::{Context, Poll};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+/// A guard that fires an exit probe with an outcome code when dropped.
+///
+/// This is usually created by [`probe_scope!`](crate::probe_scope), which
+/// fires the entry probe and supplies the exit probe to fire.
+pub struct Scope<F: FnMut(isize)> {
+    outcome: isize,
+    fire: F,
+}
+
+impl<F: FnMut(isize)> Scope<F> {
+    /// Start a scope which calls `fire` with its outcome when dropped.
+    #[inline]
+    pub fn new(fire: F) -> Self {
+        Scope { outcome: 0, fire }
+    }
+
+    /// Set the outcome code that will be reported by the exit probe.
+    #[inline]
+    pub fn set_outcome(&mut self, code: isize) {
+        self.outcome = code;
+    }
+
+    /// Returns the outcome code that will be reported by the exit probe.
+    #[inline]
+    pub fn outcome(&self) -> isize {
+        self.outcome
+    }
+}
+
+impl<F: FnMut(isize)> Drop for Scope<F> {
+    #[inline]
+    fn drop(&mut self) {
+        (self.fire)(self.outcome);
+    }
+}
+
+#[cfg(feature = "std")]
+/// A guard that fires a single probe with its elapsed time when dropped.
+///
+/// This is usually created by [`probe_timer!`](crate::probe_timer), which
+/// supplies the probe to fire. The probe's arguments are the elapsed
+/// nanoseconds since the timer was created, and an outcome code that
+/// defaults to `0` unless changed by [`set_outcome`](Timer::set_outcome).
+pub struct Timer<F: FnMut(Instant, isize)> {
+    start: Instant,
+    outcome: isize,
+    fire: F,
+}
+
+#[cfg(feature = "std")]
+impl<F: FnMut(Instant, isize)> Timer<F> {
+    /// Start a timer which calls `fire` with its start time and outcome when
+    /// dropped.
+    #[inline]
+    pub fn new(fire: F) -> Self {
+        Timer {
+            start: Instant::now(),
+            outcome: 0,
+            fire,
+        }
+    }
+
+    /// Set the outcome code that will be reported by the probe.
+    #[inline]
+    pub fn set_outcome(&mut self, code: isize) {
+        self.outcome = code;
+    }
+
+    /// Returns the outcome code that will be reported by the probe.
+    #[inline]
+    pub fn outcome(&self) -> isize {
+        self.outcome
+    }
+
+    /// Returns the instant when this timer was started.
+    #[inline]
+    pub fn start(&self) -> Instant {
+        self.start
+    }
+}
+
+#[cfg(feature = "std")]
+impl<F: FnMut(Instant, isize)> Drop for Timer<F> {
+    #[inline]
+    fn drop(&mut self) {
+        (self.fire)(self.start, self.outcome);
+    }
+}
+
+#[cfg(feature = "std")]
+/// A future that measures the time spent polling the future it wraps.
+///
+/// This is usually created by [`probe_future!`](crate::probe_future). When
+/// the inner future completes, `fire` is called with the instant it was first
+/// polled and the total time spent inside its `poll`, which excludes the time
+/// it was suspended. Each time the future returns `Pending`, `step` is called
+/// with `false`, and then with `true` when it's polled again. If the future is
+/// dropped before completing, `fire` is not called.
+pub struct TimedFuture<Fut, F, S> {
+    future: Fut,
+    start: Option<Instant>,
+    polled: Duration,
+    suspended: bool,
+    fire: F,
+    step: S,
+}
+
+#[cfg(feature = "std")]
+impl<Fut, F, S> TimedFuture<Fut, F, S>
+where
+    Fut: Future,
+    F: FnMut(Instant, Duration),
+    S: FnMut(bool),
+{
+    /// Wrap `future`, calling `fire` on completion and `step` on each
+    /// suspension and resumption.
+    #[inline]
+    pub fn new(future: Fut, fire: F, step: S) -> Self {
+        TimedFuture {
+            future,
+            start: None,
+            polled: Duration::ZERO,
+            suspended: false,
+            fire,
+            step,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<Fut, F, S> Future for TimedFuture<Fut, F, S>
+where
+    Fut: Future,
+    F: FnMut(Instant, Duration),
+    S: FnMut(bool),
+{
+    type Output = Fut::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is structurally pinned, and never moved out of
+        // `self`. The other fields are never pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        if this.suspended {
+            this.suspended = false;
+            (this.step)(true);
+        }
+        let now = Instant::now();
+        let start = *this.start.get_or_insert(now);
+        let poll = future.poll(cx);
+        this.polled += now.elapsed();
+
+        match poll {
+            Poll::Ready(output) => {
+                (this.fire)(start, this.polled);
+                Poll::Ready(output)
+            }
+            Poll::Pending => {
+                this.suspended = true;
+                (this.step)(false);
+                Poll::Pending
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+/// Returns the nanoseconds elapsed since `start`, saturating at `isize::MAX`.
+#[doc(hidden)]
+#[inline]
+pub fn elapsed_nanos(start: Instant) -> isize {
+    duration_nanos(start.elapsed())
+}
+
+#[cfg(feature = "std")]
+/// Returns the nanoseconds in `duration`, saturating at `isize::MAX`.
+#[doc(hidden)]
+#[inline]
+pub fn duration_nanos(duration: Duration) -> isize {
+    let nanos = duration.as_nanos();
+    if nanos > isize::MAX as u128 {
+        isize::MAX
+    } else {
+        nanos as isize
+    }
+}
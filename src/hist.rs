@@ -0,0 +1,334 @@
+//! Aggregating latencies in shared memory.
+//!
+//! Lightweight agents often only want the distribution of a probe's
+//! latencies, and attaching BPF to every process for that is heavy.
+//! `Histograms` is a backend that keeps a log2 histogram of the
+//! first argument of each probe that reaches it, which is the elapsed
+//! nanoseconds for [`probe_timer!`](crate::probe_timer). The histograms live
+//! in a file in `/dev/shm` at [`path`] for this process's ID, so an agent
+//! that finds a process's probes with `registry::process` can [`scrape`]
+//! their distributions without attaching anything.
+//!
+//! Bucket 0 counts values of zero or less, and bucket `i` counts values from
+//! `2^(i-1)` up to `2^i`, with the last bucket taking everything larger.
+//! Each of the first [`SLOTS`] probes to fire gets a histogram, and later
+//! probes are only counted as dropped.
+//!
+//! This module requires the `std` feature, and is only available on Linux.
+//! `Histograms` also requires the `backend` feature.
+//!
+//! # Example
+//!
+//! ```no_run
+//! let pid = 1234;
+//! for histogram in probe::hist::scrape(pid).unwrap() {
+//!     println!("{}:{} {:?}", histogram.provider, histogram.name, histogram.buckets);
+//! }
+//! ```
+
+use std::format;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::string::String;
+use std::vec::Vec;
+
+/// The number of probes that can have a histogram.
+pub const SLOTS: usize = 64;
+
+/// The number of buckets in each histogram.
+pub const BUCKETS: usize = 64;
+
+const MAGIC: [u8; 8] = *b"probehst";
+const VERSION: u32 = 1;
+const NAME_LEN: usize = 64;
+
+// The file starts with a header of the magic, the version, the number of
+// slots in use and the number of dropped firings. Each slot then has the
+// probe's `provider:name`, NUL-padded, and its buckets, all native-endian.
+const HEADER_LEN: usize = 24;
+const SLOT_LEN: usize = NAME_LEN + 8 * BUCKETS;
+
+/// The histogram of one probe's first argument.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Histogram {
+    /// The probe's provider.
+    pub provider: String,
+    /// The probe's name.
+    pub name: String,
+    /// The count in each bucket, [`BUCKETS`] of them.
+    pub buckets: Vec<u64>,
+}
+
+impl Histogram {
+    /// Returns the total count of all buckets.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().sum()
+    }
+}
+
+/// Returns the path of the histograms of the process with ID `pid`.
+pub fn path(pid: u32) -> PathBuf {
+    PathBuf::from(format!("/dev/shm/probe-{}.hist", pid))
+}
+
+/// Read the histograms of the process with ID `pid`.
+///
+/// This fails with `NotFound` if the process has no `Histograms` backend.
+pub fn scrape(pid: u32) -> io::Result<Vec<Histogram>> {
+    parse(&fs::read(path(pid))?)
+}
+
+/// Parse the contents of a histograms file.
+pub fn parse(data: &[u8]) -> io::Result<Vec<Histogram>> {
+    let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+    if data.len() < HEADER_LEN + SLOTS * SLOT_LEN || data[..8] != MAGIC {
+        return Err(invalid("not a histograms file"));
+    }
+    if u32_at(data, 8) != VERSION {
+        return Err(invalid("unknown histograms version"));
+    }
+    let used = (u32_at(data, 12) as usize).min(SLOTS);
+    let mut histograms = Vec::with_capacity(used);
+    for slot in data[HEADER_LEN..].chunks_exact(SLOT_LEN).take(used) {
+        let (name, buckets) = slot.split_at(NAME_LEN);
+        let len = name.iter().position(|&b| b == 0).unwrap_or(NAME_LEN);
+        let name = String::from_utf8_lossy(&name[..len]);
+        let (provider, name) = name.split_once(':').unwrap_or(("", &name));
+        histograms.push(Histogram {
+            provider: provider.into(),
+            name: name.into(),
+            buckets: buckets
+                .chunks_exact(8)
+                .map(|b| u64::from_ne_bytes(b.try_into().unwrap()))
+                .collect(),
+        });
+    }
+    Ok(histograms)
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_ne_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Returns the bucket that counts `value`.
+pub fn bucket(value: isize) -> usize {
+    if value <= 0 {
+        0
+    } else {
+        (usize::BITS - value.leading_zeros()).min(BUCKETS as u32 - 1) as usize
+    }
+}
+
+#[cfg(all(feature = "backend", target_has_atomic = "64"))]
+pub use self::shared::Histograms;
+
+#[cfg(all(feature = "backend", target_has_atomic = "64"))]
+mod shared {
+    use super::*;
+    use crate::backend::ProbeBackend;
+    use core::ffi::{c_int, c_long, c_void};
+    use core::{mem, ptr};
+    use std::collections::HashMap;
+    use std::fs::{File, OpenOptions};
+    use std::os::unix::io::AsRawFd;
+    use std::process;
+    use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+    use std::sync::Mutex;
+
+    #[repr(C)]
+    struct Header {
+        magic: [u8; 8],
+        version: u32,
+        used: AtomicU32,
+        dropped: AtomicU64,
+    }
+
+    #[repr(C)]
+    struct Slot {
+        name: [u8; NAME_LEN],
+        buckets: [AtomicU64; BUCKETS],
+    }
+
+    #[repr(C)]
+    struct Page {
+        header: Header,
+        slots: [Slot; SLOTS],
+    }
+
+    const _: () = assert!(mem::size_of::<Header>() == HEADER_LEN);
+    const _: () = assert!(mem::size_of::<Slot>() == SLOT_LEN);
+
+    const PROT_READ: c_int = 1;
+    const PROT_WRITE: c_int = 2;
+    const MAP_SHARED: c_int = 1;
+
+    #[cfg(target_env = "musl")]
+    type Off = i64;
+    #[cfg(not(target_env = "musl"))]
+    type Off = c_long;
+
+    extern "C" {
+        fn mmap(
+            addr: *mut c_void,
+            len: usize,
+            prot: c_int,
+            flags: c_int,
+            fd: c_int,
+            offset: Off,
+        ) -> *mut c_void;
+        fn munmap(addr: *mut c_void, len: usize) -> c_int;
+    }
+
+    /// A backend that keeps histograms of probe arguments in shared memory,
+    /// and passes every firing on to `B`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use probe::backend::{self, ProbeBackend};
+    /// use probe::hist::Histograms;
+    ///
+    /// struct Discard;
+    ///
+    /// impl ProbeBackend for Discard {
+    ///     fn fire(&self, _provider: &'static str, _name: &'static str, _args: &[isize]) {}
+    /// }
+    ///
+    /// let histograms = Histograms::new(Discard).expect("/dev/shm is unavailable");
+    /// backend::set_backend(Box::leak(Box::new(histograms))).unwrap();
+    /// let _timer = probe::probe_timer!(foo, request);
+    /// ```
+    pub struct Histograms<B> {
+        inner: B,
+        path: PathBuf,
+        page: *mut Page,
+        slots: Mutex<HashMap<(&'static str, &'static str), usize>>,
+    }
+
+    // The page is only written through atomics, or under the `slots` lock.
+    unsafe impl<B: Send> Send for Histograms<B> {}
+    unsafe impl<B: Sync> Sync for Histograms<B> {}
+
+    impl<B> Histograms<B> {
+        /// Wrap `inner`, creating the histograms file at [`path`] for this
+        /// process.
+        ///
+        /// The file is removed again when this is dropped. There should only
+        /// be one of these in a process at a time, or they'll overwrite each
+        /// other's histograms.
+        pub fn new(inner: B) -> io::Result<Histograms<B>> {
+            let path = path(process::id());
+            let file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(true)
+                .open(&path)?;
+            match map(&file) {
+                Ok(page) => Ok(Histograms {
+                    inner,
+                    path,
+                    page,
+                    slots: Mutex::new(HashMap::new()),
+                }),
+                Err(e) => {
+                    let _ = fs::remove_file(&path);
+                    Err(e)
+                }
+            }
+        }
+
+        /// Returns the wrapped backend.
+        pub fn inner(&self) -> &B {
+            &self.inner
+        }
+
+        /// Returns the number of firings that found no free slot.
+        pub fn dropped(&self) -> u64 {
+            self.header().dropped.load(Ordering::Relaxed)
+        }
+
+        fn header(&self) -> &Header {
+            // SAFETY: the page stays mapped until drop, and the header is
+            // only written before it's shared, or through atomics.
+            unsafe { &(*self.page).header }
+        }
+
+        fn record(&self, provider: &'static str, name: &'static str, value: isize) {
+            let mut slots = self.slots.lock().unwrap_or_else(|e| e.into_inner());
+            let next = slots.len();
+            let slot = match slots.get(&(provider, name)) {
+                Some(&slot) => slot,
+                None if next < SLOTS => {
+                    let mut label = [0; NAME_LEN];
+                    let full = format!("{}:{}", provider, name);
+                    let len = full.len().min(NAME_LEN);
+                    label[..len].copy_from_slice(&full.as_bytes()[..len]);
+                    // SAFETY: slots past `used` aren't read by anyone, and
+                    // this one is only named once, under the lock.
+                    unsafe { ptr::addr_of_mut!((*self.page).slots[next].name).write(label) };
+                    self.header().used.store(next as u32 + 1, Ordering::Release);
+                    slots.insert((provider, name), next);
+                    next
+                }
+                None => {
+                    self.header().dropped.fetch_add(1, Ordering::Relaxed);
+                    return;
+                }
+            };
+            drop(slots);
+            // SAFETY: the page stays mapped until drop, and buckets are atomic.
+            let counter = unsafe { &(*self.page).slots[slot].buckets[bucket(value)] };
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    fn map(file: &File) -> io::Result<*mut Page> {
+        let len = mem::size_of::<Page>();
+        file.set_len(len as u64)?;
+        // SAFETY: a fresh shared mapping of a file of the right length.
+        let page = unsafe {
+            mmap(
+                ptr::null_mut(),
+                len,
+                PROT_READ | PROT_WRITE,
+                MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if page as isize == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let page = page.cast::<Page>();
+        // SAFETY: the mapping is zeroed, so only the header needs writing,
+        // and nothing else has it yet.
+        unsafe {
+            ptr::addr_of_mut!((*page).header.magic).write(MAGIC);
+            ptr::addr_of_mut!((*page).header.version).write(VERSION);
+        }
+        Ok(page)
+    }
+
+    impl<B> Drop for Histograms<B> {
+        fn drop(&mut self) {
+            let _ = fs::remove_file(&self.path);
+            // SAFETY: nothing can use the page after this.
+            unsafe { munmap(self.page.cast(), mem::size_of::<Page>()) };
+        }
+    }
+
+    impl<B: ProbeBackend> ProbeBackend for Histograms<B> {
+        fn enabled(&self, provider: &'static str, name: &'static str) -> bool {
+            self.inner.enabled(provider, name)
+        }
+
+        fn fire(&self, provider: &'static str, name: &'static str, args: &[isize]) {
+            if let Some(&value) = args.first() {
+                self.record(provider, name, value);
+            }
+            self.inner.fire(provider, name, args);
+        }
+    }
+}
@@ -0,0 +1,101 @@
+//! Interned labels for dynamic strings.
+//!
+//! Probe arguments are integers, so a dynamic string like a queue name or an
+//! endpoint would otherwise be passed as a pointer that the tracer copies on
+//! every firing. Instead, [`intern`] gives each distinct string a stable `u32`
+//! id once, and probes can pass that id. The table of ids is reported through
+//! probes in the reserved `rust_intern` provider:
+//!
+//! | probe    | arguments               | fired by                  |
+//! |----------|-------------------------|---------------------------|
+//! | `label`  | `id`, `ptr`, `len`      | [`intern`] and [`dump`]   |
+//!
+//! The string is `len` bytes of UTF-8 at `ptr`, for instance
+//! `str(arg1, arg2)` in bpftrace. `label` fires when a new string is first
+//! interned, and [`dump`] fires it again for the whole table, for tracers
+//! that attach later. Interned strings are never freed, so this is meant for
+//! a bounded set of labels.
+//!
+//! This module requires the `std` feature.
+//!
+//! # Example
+//!
+//! ```
+//! # use probe::probe;
+//! let queue = probe::intern::intern("billing");
+//! assert_eq!(probe::intern::intern("billing"), queue);
+//! assert_eq!(probe::intern::lookup(queue), Some("billing"));
+//! probe!(app, enqueue, queue);
+//! ```
+
+use crate::probe;
+use std::boxed::Box;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+use std::vec::Vec;
+
+/// The name of the provider for these probes, in the reserved `rust_*`
+/// namespace.
+pub const PROVIDER: &str = "rust_intern";
+
+struct Table {
+    ids: BTreeMap<&'static str, u32>,
+    labels: Vec<&'static str>,
+}
+
+static TABLE: Mutex<Table> = Mutex::new(Table {
+    ids: BTreeMap::new(),
+    labels: Vec::new(),
+});
+
+fn lock() -> MutexGuard<'static, Table> {
+    // The table is always consistent between operations, even after a panic.
+    TABLE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[inline]
+fn fire(id: u32, label: &str) {
+    probe!(rust_intern, label, id, label.as_ptr(), label.len());
+}
+
+/// Returns the id of `label`, interning it first if it's new.
+///
+/// Ids are assigned in order from zero, and stay the same for the life of the
+/// process. Interning a new string fires `rust_intern:label`.
+#[inline]
+pub fn intern(label: &str) -> u32 {
+    // The probe is only instantiated in the caller, so binaries that never
+    // intern anything don't carry its note.
+    let (id, new) = insert(label);
+    if let Some(label) = new {
+        fire(id, label);
+    }
+    id
+}
+
+/// Returns the id of `label`, and the interned copy if it's new.
+fn insert(label: &str) -> (u32, Option<&'static str>) {
+    let mut table = lock();
+    if let Some(&id) = table.ids.get(label) {
+        return (id, None);
+    }
+    let id = u32::try_from(table.labels.len()).expect("too many interned labels");
+    let label: &'static str = Box::leak(label.into());
+    table.ids.insert(label, id);
+    table.labels.push(label);
+    (id, Some(label))
+}
+
+/// Returns the string interned as `id`, if any.
+pub fn lookup(id: u32) -> Option<&'static str> {
+    lock().labels.get(id as usize).copied()
+}
+
+/// Fire `rust_intern:label` for every interned string, in id order.
+#[inline]
+pub fn dump() {
+    let labels = lock().labels.clone();
+    for (id, label) in labels.into_iter().enumerate() {
+        fire(id as u32, label);
+    }
+}
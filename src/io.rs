@@ -0,0 +1,55 @@
+//! Probes around I/O submission and system calls.
+//!
+//! These helpers fire paired probes in the `rust_io` provider with a fixed
+//! argument layout, so one BPF or SystemTap script can correlate them with the
+//! kernel's own tracepoints for any program using this crate:
+//!
+//! | probe            | arguments               | fired by            |
+//! |------------------|-------------------------|---------------------|
+//! | `submit`         | `user_data`, `opcode`   | [`submit`]          |
+//! | `complete`       | `user_data`, `result`   | [`complete`]        |
+//! | `syscall_enter`  | `nr`                    | [`syscall`]         |
+//! | `syscall_exit`   | `nr`, `result`          | [`syscall`]         |
+//!
+//! The `user_data` of an asynchronous operation, like the field of the same
+//! name in an `io_uring` submission entry, is the correlation key between the
+//! `submit` and `complete` probes, and with the kernel's `io_uring:*`
+//! tracepoints. Synchronous system calls can be joined to
+//! `raw_syscalls:sys_enter` and `sys_exit` by thread and `nr`.
+
+use crate::probe;
+
+/// The name of the provider for these probes, in the reserved `rust_*`
+/// namespace.
+pub const PROVIDER: &str = "rust_io";
+
+/// Fire `rust_io:submit` as an asynchronous operation is submitted.
+#[inline]
+pub fn submit(user_data: u64, opcode: u8) {
+    probe!(rust_io, submit, user_data, opcode);
+}
+
+/// Fire `rust_io:complete` as the completion of an operation is reaped.
+#[inline]
+pub fn complete(user_data: u64, result: i32) {
+    probe!(rust_io, complete, user_data, result);
+}
+
+/// Call `f` between `rust_io:syscall_enter` and `rust_io:syscall_exit` probes.
+///
+/// The exit probe carries the return value of `f`, which should be the raw
+/// result of system call number `nr`.
+///
+/// # Example
+///
+/// ```
+/// let result = probe::io::syscall(39, || std::process::id() as isize);
+/// assert_eq!(result, std::process::id() as isize);
+/// ```
+#[inline]
+pub fn syscall(nr: isize, f: impl FnOnce() -> isize) -> isize {
+    probe!(rust_io, syscall_enter, nr);
+    let result = f();
+    probe!(rust_io, syscall_exit, nr, result);
+    result
+}
@@ -77,10 +77,179 @@
 //! (gdb) print $_probe_arg1
 //! $2 = 1035
 //! ```
+//!
+//! ## Reserved `rust_*` providers
+//!
+//! Provider names starting with `rust_` are reserved for the probes this crate
+//! fires itself, so that shared bpftrace and SystemTap libraries can target
+//! any Rust program using it. Their probe names and argument layouts are part
+//! of this crate's stable API: probes may be added, but existing ones won't be
+//! renamed or have their arguments changed without a semver-breaking release.
+//!
+//! | provider      | probes                                                  | module   |
+//! |---------------|---------------------------------------------------------|----------|
+//! | `rust_io`     | `submit`, `complete`, `syscall_enter`, `syscall_exit`   | [`io`]   |
+//! | `rust_epoch`  | `advance`, `flush_begin`, `flush_end`                   | `epoch`  |
+//! | `rust_intern` | `label`                                                 | `intern` |
+//! | `rust_bench`  | `nop`, `lazy`, `args`                                   | `bench`  |
+//! | `rust_mem`    | `stats`                                                 | `mem`    |
+//!
+//! Each module documents the arguments of its probes. Applications should
+//! choose their own provider names outside this namespace.
+//!
+//! ## Allocators and thread teardown
+//!
+//! Firing a probe never allocates or touches thread-local storage, so probes
+//! can be placed inside a `GlobalAlloc` implementation or a thread-local
+//! destructor without deadlocking. The one exception is `#[baggage]`, whose
+//! thread-local is never allocated and simply reads zero after the thread's
+//! storage is gone. With the `backend` feature, an installed backend must
+//! make the same guarantees if it's to see those probes, which rules out each
+//! of the backends in this crate:
+//!
+//! * `throttle::Throttle` and `stats::Hits` update a map under a lock.
+//! * `user_events::UserEvents` registers each new probe under a lock, which
+//!   allocates a map entry and the event's description.
+//! * `atrace::ATrace` formats a `String` for each firing while tracing.
+//!
+//! ## Sanitizers
+//!
+//! When the crate is built with `-Zsanitizer`, probes use the no-op
+//! implementation instead, as sanitizers can't account for the inline
+//! assembly at each probe site or for semaphores written by debuggers.
+//! Arguments are still evaluated the same way.
+//!
+//! ## Unsupported platforms
+//!
+//! On targets without a native implementation, probes are silently no-ops, as
+//! [`is_supported`] reports at runtime. To fail the build instead, enable the
+//! `require-native` feature, which makes selecting the no-op implementation a
+//! compile error naming the target. Sanitizer builds are still allowed.
+//!
+//! ## Build-time host tools
+//!
+//! An instrumented library may also be compiled into build scripts and
+//! proc-macros, which don't need probes. The `noop` feature compiles every
+//! probe to the no-op implementation, like sanitizer builds. With Cargo's
+//! feature resolver version 2, build dependencies get their own features. So
+//! a crate can enable `noop` for its build tools alone, and keep probes in
+//! the real target:
+//!
+//! ```toml
+//! [build-dependencies]
+//! probe = { version = "0.5", features = ["noop"] }
+//! ```
+//!
+//! Proc-macro crates can enable it in their normal dependencies the same way.
+//! In any one dependency graph, `noop` applies to every probe in it.
+//!
+//! ## Static binaries without a base
+//!
+//! SystemTap notes normally refer to a shared `_.stapsdt.base` symbol, so tools
+//! can correct addresses after prelinking. For static binaries that are never
+//! prelinked, the `no-base` feature leaves it out, and notes record a zero
+//! base instead. That saves a section and a weak symbol that some embedded
+//! linkers mishandle. Only use it when nothing else in the link, like C code
+//! using `sys/sdt.h`, defines `.stapsdt.base`, or tools will misplace probes.
+//! `registry::versions` reports which kind of notes a binary has.
 
 #![no_std]
 
-mod platform;
+#[cfg(feature = "std")]
+extern crate std;
+
+#[cfg(all(feature = "backend", feature = "std", target_os = "android"))]
+pub mod atrace;
+#[cfg(feature = "backend")]
+pub mod backend;
+#[cfg(feature = "std")]
+pub mod baggage;
+pub mod batch;
+#[cfg(feature = "std")]
+pub mod bench;
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+pub mod consumer;
+#[cfg(feature = "backend")]
+pub mod control;
+#[cfg(feature = "epoch")]
+pub mod epoch;
+mod frontend;
+pub mod guard;
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+pub mod hist;
+#[cfg(feature = "std")]
+pub mod intern;
+pub mod io;
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+pub mod mem;
+#[doc(hidden)]
+pub mod platform;
+pub mod preview;
+pub mod provider;
+#[cfg(feature = "std")]
+pub mod registry;
+mod semaphore;
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+pub mod stats;
+pub mod support;
+#[cfg(all(feature = "backend", feature = "std"))]
+pub mod throttle;
+#[cfg(all(
+    feature = "backend",
+    feature = "std",
+    any(target_os = "linux", target_os = "android")
+))]
+pub mod user_events;
+#[cfg(feature = "std")]
+pub mod wake;
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+pub mod watch;
+
+#[cfg(feature = "std")]
+pub use registry::verify_binary;
+pub use semaphore::Semaphore;
+pub use support::is_supported;
+
+/// Stop this crate's background threads, and release what they hold.
+///
+/// This stops the threads of [`watch`] hooks and [`stats`] servers, drops
+/// the hooks and closes the listeners, and resets the hit counts, so a
+/// library can be unloaded with `dlclose`, or a test can start over. Each
+/// subsystem starts again the next time it's used.
+///
+/// This waits up to [`SHUTDOWN_TIMEOUT`] for the threads to finish a hook
+/// that's running or a response that's being sent. A thread that's still
+/// busy then, like one running a hook that never returns, is left to exit on
+/// its own when it's done. This must not be called from a watch hook.
+///
+/// An installed backend stays in place, since it's `'static`, and so do
+/// interned labels, whose ids are stable for the life of the process.
+///
+/// This requires the `std` feature, and does nothing but on Linux.
+#[cfg(feature = "std")]
+pub fn shutdown() {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let deadline = std::time::Instant::now() + SHUTDOWN_TIMEOUT;
+        watch::shutdown(deadline);
+        stats::shutdown(deadline);
+    }
+}
+
+/// The longest that [`shutdown`] waits for background threads.
+#[cfg(feature = "std")]
+pub const SHUTDOWN_TIMEOUT: core::time::Duration = core::time::Duration::from_secs(2);
+
+/// Join `handle` if it finishes by `deadline`, or else leave it detached.
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+fn join(handle: std::thread::JoinHandle<()>, deadline: std::time::Instant) {
+    while !handle.is_finished() && std::time::Instant::now() < deadline {
+        std::thread::sleep(core::time::Duration::from_millis(5));
+    }
+    if handle.is_finished() {
+        let _ = handle.join();
+    }
+}
 
 /// Define a static probe point.
 ///
@@ -96,6 +265,56 @@ mod platform;
 /// * `arg`...   - Optional data to provide with the probe. Any expression which
 ///   can be cast `as isize` is allowed as an argument. The arguments are always
 ///   evaluated, even on platforms that have a no-op implementation of probes.
+///   Wrapper types don't cast, so pass what they wrap: `n.get()` for a
+///   `NonZeroU32`, or the field of a newtype like `Fd(i32)`.
+///
+/// # Argument evaluation
+///
+/// On every platform, each argument is evaluated exactly once, from left to
+/// right, before the probe point itself is reached. This happens whether or not
+/// any tool is attached. If an argument expression panics or otherwise diverges,
+/// the remaining arguments are not evaluated and the probe does not fire.
+/// Operators like `&&` and `||` short-circuit within an argument as usual.
+///
+/// # Debugger-visible semaphores
+///
+/// GDB's `info probes` only shows a semaphore for lazy probes. With a leading
+/// `#[semaphore]`, an eager probe also gets one, purely so it's listed there
+/// and debugging workflows can treat all probes alike. The probe still fires
+/// unconditionally, and platforms without semaphores ignore the attribute.
+/// To apply this to a whole provider, wrap it in a macro of your own:
+///
+/// ```
+/// macro_rules! db_probe {
+///     ($name:ident $(, $arg:expr)*) => (probe::probe!(#[semaphore] db, $name $(, $arg)*));
+/// }
+/// db_probe!(query, 42);
+/// ```
+///
+/// # Ordering
+///
+/// A probe only reads its arguments, so the compiler is free to move
+/// surrounding loads and stores across it. When a probe marks a step of a
+/// lock-free protocol, a leading `#[ordered]` makes it a compiler barrier, so a
+/// tracer sees every store made before the probe and none made after it. Use
+/// `#[ordered(fence)]` to also issue a sequentially consistent CPU fence on
+/// both sides, for when other threads' view of those stores matters too. Any
+/// `#[semaphore]` comes after the ordering attribute.
+///
+/// ```
+/// # use probe::probe;
+/// # use std::sync::atomic::{AtomicUsize, Ordering};
+/// let head = AtomicUsize::new(0);
+/// head.store(1, Ordering::Relaxed);
+/// probe!(#[ordered] queue, publish, 1);
+/// probe!(#[ordered(fence)] #[semaphore] queue, publish_fenced, 2);
+/// ```
+///
+/// # Baggage
+///
+/// With the `std` feature, a leading `#[baggage]` appends the current thread's
+/// `baggage` value as one more argument, after the others. It comes before
+/// any other attributes.
 ///
 /// # Example
 ///
@@ -119,8 +338,23 @@ mod platform;
 /// ```
 #[macro_export]
 macro_rules! probe(
+    (#[baggage] $(#[$($attr:tt)*])* $provider:ident, $name:ident $(, $arg:expr)* $(,)?)
+    => ($crate::probe!($(#[$($attr)*])* $provider, $name $(, $arg)*,
+            $crate::baggage::get()));
+
+    (#[ordered $(($fence:ident))?] $(#[$attr:ident])? $provider:ident, $name:ident
+        $(, $arg:expr)* $(,)?
+    ) => ({
+        $crate::probe_fence!($($fence)?);
+        $crate::probe!($(#[$attr])? $provider, $name $(, $arg)*);
+        $crate::probe_fence!($($fence)?);
+    });
+
+    (#[semaphore] $provider:ident, $name:ident $(, $arg:expr)* $(,)?)
+    => ($crate::backend_probe!(#[semaphore] $provider, $name, $($arg,)*));
+
     ($provider:ident, $name:ident $(, $arg:expr)* $(,)?)
-    => ($crate::platform_probe!($provider, $name, $($arg,)*));
+    => ($crate::backend_probe!($provider, $name, $($arg,)*));
 );
 
 /// Define a static probe point with lazy argument evaluation.
@@ -132,6 +366,44 @@ macro_rules! probe(
 /// can't determine that, it might always evaluate arguments.
 ///
 /// Returns `true` if the probe is executed (and its arguments evaluated).
+/// This contract is the same on every platform: on the no-op platform it is
+/// always `false`, and on WASI hosts it is whatever the host's `enabled`
+/// import returns, so callers can rely on it without any `cfg`.
+///
+/// When the probe is executed, its arguments follow the same rules as
+/// [`probe!`](macro.probe.html#argument-evaluation): each is evaluated exactly
+/// once, from left to right. Otherwise, none of them are evaluated at all.
+///
+/// # Exported semaphores
+///
+/// The semaphore may be given a stable symbol name with a leading
+/// `#[export_name = "..."]`, so foreign code in the same process can check
+/// the very same flag before collecting its own data, for example with
+/// `extern unsigned short foo_main_semaphore;` in C. The semaphore is an
+/// unsigned 16-bit counter that is non-zero while a tool is attached. Each
+/// exported name must only be used by a single probe site, or the link will
+/// fail with duplicate symbols. Platforms without semaphore support still
+/// define the symbol, but it always reads zero.
+///
+/// A leading `#[baggage]` works the same as for [`probe!`], before any
+/// `#[export_name]`, and the baggage is only read when the probe is executed.
+///
+/// # Switching a provider to lazy
+///
+/// There's no feature or setting that makes a provider lazy: whether a probe
+/// is eager or lazy is fixed by the macro at each call site. If a provider's
+/// probes all go through a macro of your own, though, changing that one
+/// definition flips them all, for instance after profiling shows the
+/// arguments are costly:
+///
+/// ```
+/// macro_rules! db_probe {
+///     ($name:ident $(, $arg:expr)*) => {{
+///         let _ = probe::probe_lazy!(db, $name $(, $arg)*);
+///     }};
+/// }
+/// db_probe!(query, 42);
+/// ```
 ///
 /// # Example
 ///
@@ -143,9 +415,351 @@ macro_rules! probe(
 /// let mut z = 0;
 /// probe_lazy!(foo, inc_z, { z += 1; z });
 /// assert_eq!(z, 0, "arguments are not evaluated by default");
+///
+/// probe_lazy!(#[export_name = "foo_exported_semaphore"] foo, exported, z);
 /// ```
 #[macro_export]
 macro_rules! probe_lazy(
+    (#[baggage] $(#[$($attr:tt)*])* $provider:ident, $name:ident $(, $arg:expr)* $(,)?)
+    => ($crate::probe_lazy!($(#[$($attr)*])* $provider, $name $(, $arg)*,
+            $crate::baggage::get()));
+
+    (#[export_name = $export:literal] $provider:ident, $name:ident $(, $arg:expr)* $(,)?)
+    => ($crate::backend_probe_lazy!(#[export_name = $export] $provider, $name, $($arg,)*));
+
     ($provider:ident, $name:ident $(, $arg:expr)* $(,)?)
-    => ($crate::platform_probe_lazy!($provider, $name, $($arg,)*));
+    => ($crate::backend_probe_lazy!($provider, $name, $($arg,)*));
+);
+
+/// Define a lazy probe with basic and verbose tiers of arguments.
+///
+/// This fires the lazy probe `name` with the basic arguments followed by the
+/// verbose ones, which are all evaluated only when `name` is enabled, like
+/// [`probe_lazy!`]. The verbose arguments are also gated by a second flag: the
+/// argument-less lazy probe `verbose`, fired from the same place. They're
+/// evaluated only while a tool has that probe enabled too, and are passed as
+/// zero otherwise. So routine tracing of `name` stays cheap, while a
+/// deep-debug session enables both to see the expensive context at the same
+/// probe site. Each argument is still evaluated at most once, from left to
+/// right, and every argument is cast to `isize`.
+///
+/// Returns `true` if `name` is executed.
+///
+/// # Example
+///
+/// ```
+/// # use probe::probe_tiered;
+/// fn query(id: u64, sql: &str) {
+///     probe_tiered!(db, query, query_verbose, id, sql.len(); {
+///         let text = sql.to_uppercase();
+///         text.len()
+///     });
+/// }
+/// # query(1, "select 1");
+/// ```
+#[macro_export]
+macro_rules! probe_tiered(
+    ($provider:ident, $name:ident, $verbose:ident $(, $arg:expr)* ; $($extra:expr),* $(,)?)
+    => ({
+        let verbose = $crate::probe_lazy!($provider, $verbose);
+        $crate::probe_lazy!($provider, $name $(, $arg)*
+            $(, if verbose { ($extra) as isize } else { 0 })*)
+    });
+);
+
+/// Fire an entry probe, and return a guard that fires an exit probe when dropped.
+///
+/// The `enter` probe fires immediately with any given arguments, following the
+/// same rules as [`probe!`]. The returned [`guard::Scope`] fires the `exit`
+/// probe once when it is dropped, with a single argument for the outcome code,
+/// which is `0` unless changed by
+/// [`Scope::set_outcome`](guard::Scope::set_outcome).
+///
+/// # Example
+///
+/// ```
+/// # use probe::probe_scope;
+/// fn parse(input: &str) -> Result<i32, std::num::ParseIntError> {
+///     let mut scope = probe_scope!(foo, parse_enter, parse_exit, input.len());
+///     let result = input.parse();
+///     if result.is_err() {
+///         scope.set_outcome(-1);
+///     }
+///     result
+/// }
+/// # parse("42").unwrap();
+/// ```
+#[macro_export]
+macro_rules! probe_scope(
+    ($provider:ident, $enter:ident, $exit:ident $(, $arg:expr)* $(,)?)
+    => ({
+        $crate::probe!($provider, $enter $(, $arg)*);
+        $crate::guard::Scope::new(|outcome: isize| {
+            $crate::probe!($provider, $exit, outcome);
+        })
+    });
+);
+
+/// Start a timer that fires a probe with the elapsed time when dropped.
+///
+/// This returns a [`guard::Timer`] for the given `provider` and `name`. When
+/// the timer is dropped, the probe fires once with two arguments: the elapsed
+/// nanoseconds, and an outcome code that may be changed with
+/// [`Timer::set_outcome`](guard::Timer::set_outcome) beforehand. Like
+/// [`probe_lazy!`], the elapsed time is only computed while the probe is
+/// enabled, but the start time is always recorded.
+///
+/// This requires the `std` feature.
+///
+/// # Example
+///
+/// ```
+/// # use probe::probe_timer;
+/// fn work() -> Result<(), ()> {
+///     let mut timer = probe_timer!(foo, work);
+///     let result = Ok(());
+///     timer.set_outcome(if result.is_ok() { 0 } else { -1 });
+///     result
+/// }
+/// # work().unwrap();
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! probe_timer(
+    ($provider:ident, $name:ident $(,)?)
+    => ($crate::guard::Timer::new(|start, outcome: isize| {
+        $crate::probe_lazy!($provider, $name, $crate::guard::elapsed_nanos(start), outcome);
+    }));
+);
+
+/// Derive the stable [`provider::Guid`] for a provider identifier.
+///
+/// This can be used in constants, for example to configure an ETW session
+/// ahead of time with the same identity that probes would use.
+///
+/// # Example
+///
+/// ```
+/// # use probe::provider_guid;
+/// const FOO: probe::provider::Guid = provider_guid!(foo);
+/// assert_eq!(FOO, probe::provider::Guid::from_name("FOO"));
+/// ```
+#[macro_export]
+macro_rules! provider_guid(
+    ($provider:ident) => ($crate::provider::Guid::from_name(::core::stringify!($provider)));
+);
+
+/// Wrap a future to fire a probe with its polled and elapsed time on completion.
+///
+/// This returns a [`guard::TimedFuture`], which fires the `name` probe once
+/// when the future completes, with two arguments: the nanoseconds actually
+/// spent polling the future, and the wall-clock nanoseconds since it was
+/// first polled. Unlike a [`probe_timer!`] held across `.await`, the first
+/// argument excludes the time the task was suspended.
+///
+/// With the optional `suspend` and `resume` names, those probes also fire
+/// without arguments each time the future returns `Pending`, and each time it
+/// is polled again afterward.
+///
+/// This requires the `std` feature.
+///
+/// # Example
+///
+/// ```
+/// # use probe::probe_future;
+/// async fn fetch() -> u32 {
+///     42
+/// }
+///
+/// async fn handler() -> u32 {
+///     probe_future!(foo, fetch, fetch(), fetch_suspend, fetch_resume).await
+/// }
+/// # let _ = handler();
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! probe_future(
+    ($provider:ident, $name:ident, $future:expr $(,)?)
+    => ($crate::guard::TimedFuture::new(
+        $future,
+        |start, polled| {
+            $crate::probe_lazy!($provider, $name,
+                $crate::guard::duration_nanos(polled), $crate::guard::elapsed_nanos(start));
+        },
+        |_| {},
+    ));
+
+    ($provider:ident, $name:ident, $future:expr, $suspend:ident, $resume:ident $(,)?)
+    => ($crate::guard::TimedFuture::new(
+        $future,
+        |start, polled| {
+            $crate::probe_lazy!($provider, $name,
+                $crate::guard::duration_nanos(polled), $crate::guard::elapsed_nanos(start));
+        },
+        |resumed| {
+            if resumed {
+                $crate::probe!($provider, $resume);
+            } else {
+                $crate::probe!($provider, $suspend);
+            }
+        },
+    ));
+);
+
+/// Wrap a future to fire a probe with its wake-to-poll latency.
+///
+/// This returns a [`wake::WakeLatency`], which fires the `name` probe each
+/// time the future is polled after being woken, with the nanoseconds between
+/// the wake and that poll. This is the scheduling delay of the executor.
+///
+/// This requires the `std` feature.
+///
+/// # Example
+///
+/// ```
+/// # use probe::probe_wake;
+/// async fn task() {}
+///
+/// let future = probe_wake!(foo, scheduled, task());
+/// # let _ = future;
+/// ```
+#[cfg(feature = "std")]
+#[macro_export]
+macro_rules! probe_wake(
+    ($provider:ident, $name:ident, $future:expr $(,)?)
+    => ($crate::wake::WakeLatency::new($future, |woken| {
+        $crate::probe_lazy!($provider, $name, $crate::guard::elapsed_nanos(woken));
+    }));
+);
+
+/// Collect a value into a batch, firing one probe for each full batch.
+///
+/// With a value, this pushes it into the given [`batch::Batch`], and when the
+/// batch is full, fires the probe with two arguments: a pointer to the packed
+/// `isize` values, and their count. The batch is cleared each time it fills,
+/// whether or not anything is attached. Without a value, this fires the probe
+/// for any values collected so far, for instance before a thread exits.
+///
+/// The probe is lazy, so its pointer and count are only computed when it's
+/// enabled, but values are always collected.
+///
+/// # Example
+///
+/// ```
+/// # use probe::probe_batch;
+/// use probe::batch::Batch;
+///
+/// let mut batch = Batch::<64>::new();
+/// for i in 0..1000 {
+///     probe_batch!(foo, items, batch, i);
+/// }
+/// probe_batch!(foo, items, batch);
+/// assert!(batch.is_empty());
+/// ```
+#[macro_export]
+macro_rules! probe_batch(
+    ($provider:ident, $name:ident, $batch:expr, $value:expr $(,)?) => ({
+        let batch = &mut $batch;
+        if batch.push(($value) as isize) {
+            $crate::probe_batch!($provider, $name, *batch);
+        }
+    });
+
+    ($provider:ident, $name:ident, $batch:expr $(,)?) => ({
+        let batch = &mut $batch;
+        if !batch.is_empty() {
+            let values = batch.as_slice();
+            $crate::probe_lazy!($provider, $name, values.as_ptr(), values.len());
+            batch.clear();
+        }
+    });
+);
+
+/// Preview the argument encoding of a probe, without emitting it.
+///
+/// This returns a [`preview::Preview`] describing the probe's SDT argument
+/// string, for unit tests to check against what their SystemTap scripts
+/// expect. The argument expressions are not evaluated.
+///
+/// # Example
+///
+/// ```
+/// # use probe::probe_preview;
+/// let x = 42u8;
+/// let preview = probe_preview!(foo, bar, x, -1);
+/// assert_eq!(preview.args, 2);
+/// if cfg!(target_pointer_width = "64") {
+///     assert_eq!(preview.to_string(), "-8@%reg -8@%reg");
+///     assert!(preview.matches("-8@%rax -8@%rdx"));
+/// }
+/// ```
+#[macro_export]
+macro_rules! probe_preview(
+    ($provider:ident, $name:ident $(, $arg:expr)* $(,)?) => ({
+        // Type-check the arguments the same way a real probe would.
+        if false {
+            let _ = ($(($arg) as isize,)*);
+        }
+        $crate::preview::Preview {
+            provider: ::core::stringify!($provider),
+            name: ::core::stringify!($name),
+            args: <[&str]>::len(&[$(::core::stringify!($arg)),*]),
+        }
+    });
+);
+
+/// Declare a group of related probes as a module of typed functions.
+///
+/// The module is named after the provider, and has one `#[inline]` function
+/// per probe that fires it with its arguments, so call sites don't repeat the
+/// provider name or argument casts. The module also gets a `PROVIDER` constant
+/// with the provider's name, and `NAMES` listing its probes. Argument types
+/// are resolved in the enclosing module, and must be castable `as isize`.
+///
+/// # Example
+///
+/// ```
+/// probe::probe_group! {
+///     /// Probes for the query engine.
+///     pub db {
+///         fn query_start(id: u64);
+///         fn query_done(id: u64, rows: usize);
+///         fn reset();
+///     }
+/// }
+///
+/// db::query_start(7);
+/// db::query_done(7, 42);
+/// assert_eq!(db::PROVIDER, "db");
+/// assert_eq!(db::NAMES, ["query_start", "query_done", "reset"]);
+/// ```
+#[macro_export]
+macro_rules! probe_group(
+    ($(#[$attr:meta])* $vis:vis $provider:ident {
+        $($(#[$probe_attr:meta])* fn $name:ident($($arg:ident: $ty:ty),* $(,)?);)*
+    }) => (
+        $(#[$attr])*
+        $vis mod $provider {
+            #[allow(unused_imports)]
+            use super::*;
+
+            /// The name of the provider for these probes.
+            pub const PROVIDER: &str = ::core::stringify!($provider);
+
+            /// The names of all probes in this group.
+            pub const NAMES: &[&str] = &[$(::core::stringify!($name)),*];
+
+            $(
+                $(#[$probe_attr])*
+                #[doc = ::core::concat!(
+                    "Fire the `", ::core::stringify!($provider), ":",
+                    ::core::stringify!($name), "` probe."
+                )]
+                #[inline]
+                pub fn $name($($arg: $ty),*) {
+                    $crate::probe!($provider, $name $(, $arg)*);
+                }
+            )*
+        }
+    );
 );
@@ -0,0 +1,103 @@
+//! Reporting allocator statistics to tracers.
+//!
+//! Allocators like jemalloc and mimalloc keep statistics that explain a
+//! process's memory use far better than its RSS, but they're usually only
+//! exported through an HTTP endpoint or a log. [`report`] fires them as the
+//! `rust_mem:stats` probe instead, from a background thread that only runs
+//! while a tool is attached to that probe. It fires once right away, so
+//! attaching briefly takes a sample on demand, and then every interval until
+//! the last tool detaches.
+//!
+//! The arguments of `rust_mem:stats` are the [`Stats`] fields, in order:
+//! `allocated`, `active` and `resident`, all in bytes. The application
+//! supplies them, since this crate doesn't depend on any allocator, usually
+//! from its allocator's control interface, like `tikv-jemalloc-ctl`'s
+//! `stats` module.
+//!
+//! This module requires the `std` feature, and is only available on Linux.
+//!
+//! # Example
+//!
+//! ```
+//! use probe::mem::{self, Stats};
+//! use std::time::Duration;
+//!
+//! # if probe::is_supported() {
+//! mem::report(Duration::from_secs(1), || Stats {
+//!     allocated: 1 << 20,
+//!     active: 2 << 20,
+//!     resident: 3 << 20,
+//! })
+//! .unwrap();
+//! # }
+//! ```
+//!
+//! ```notrust
+//! # bpftrace -e 'usdt:./app:rust_mem:stats { printf("%d %d\n", arg0, arg2); }'
+//! ```
+
+use crate::probe_lazy;
+use crate::watch::{self, WatchError};
+use std::string::String;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// The name of the provider for these probes, in the reserved `rust_*`
+/// namespace.
+pub const PROVIDER: &str = "rust_mem";
+
+/// A sample of an allocator's statistics, in bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Bytes allocated by the application.
+    pub allocated: usize,
+    /// Bytes in pages the allocator has in use, including fragmentation
+    /// within them.
+    pub active: usize,
+    /// Bytes of physical memory the allocator has mapped.
+    pub resident: usize,
+}
+
+impl Stats {
+    /// Returns the bytes of active pages that aren't allocated.
+    pub fn fragmentation(&self) -> usize {
+        self.active.saturating_sub(self.allocated)
+    }
+}
+
+/// Fire `rust_mem:stats` with the result of `stats` every `interval`, while
+/// a tool is attached to it.
+///
+/// `stats` is only called while a tool is attached. Each call to this starts
+/// another report, though one is usually enough. This fails if the probe
+/// has no semaphore to watch, which is when probes aren't supported.
+// Inlined, like the other probes of this crate, so that only binaries that
+// report carry the notes of `rust_mem`.
+#[inline]
+pub fn report<F>(interval: Duration, stats: F) -> Result<(), WatchError>
+where
+    F: FnMut() -> Stats + Send + 'static,
+{
+    let stats = Arc::new(Mutex::new(stats));
+    // Bumped on every change, which stops the thread of an older attach.
+    let generation = Arc::new(AtomicUsize::new(0));
+    watch::on_change(PROVIDER, "stats", move |attached| {
+        let current = generation.fetch_add(1, Ordering::Relaxed) + 1;
+        if !attached {
+            return;
+        }
+        let stats = Arc::clone(&stats);
+        let generation = Arc::clone(&generation);
+        let _ = thread::Builder::new()
+            .name(String::from("probe-mem"))
+            .spawn(move || {
+                while generation.load(Ordering::Relaxed) == current {
+                    let s = (*stats.lock().unwrap_or_else(|e| e.into_inner()))();
+                    probe_lazy!(rust_mem, stats, s.allocated, s.active, s.resident);
+                    thread::sleep(interval);
+                }
+            });
+    })
+}
@@ -1,19 +1,43 @@
+// Sanitizer and `noop` builds are exempt, since they come here on purpose.
+#[cfg(all(feature = "require-native", not(probe_noop)))]
+compile_error!(concat!(
+    "probe: the `require-native` feature is enabled, but target `",
+    env!("PROBE_TARGET"),
+    "` has no native probe support"
+));
+
+pub(crate) const PLATFORM: crate::support::Platform = crate::support::Platform::NoOp;
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! platform_probe(
     ($provider:ident, $name:ident, $($arg:expr,)*) => ({
-        // Non-lazy probes always evaluate the arguments.
-        let _ = ($($arg,)*);
-    })
+        // Non-lazy probes always evaluate the arguments, in order, with the
+        // same `isize` casts as real probes so every platform type-checks alike.
+        let _ = ($(($arg) as isize,)*);
+    });
+
+    (#[semaphore] $provider:ident, $name:ident, $($arg:expr,)*)
+    => ($crate::platform_probe!($provider, $name, $($arg,)*));
 );
 
 #[doc(hidden)]
 #[macro_export]
 macro_rules! platform_probe_lazy(
+    (#[export_name = $export:literal] $provider:ident, $name:ident, $($arg:expr,)*) => ({
+        // Foreign code may still refer to the exported semaphore, which is
+        // simply never enabled here.
+        {
+            #[export_name = $export]
+            static SEMAPHORE: $crate::Semaphore = $crate::Semaphore::new();
+        }
+        $crate::platform_probe_lazy!($provider, $name, $($arg,)*)
+    });
+
     ($provider:ident, $name:ident, $($arg:expr,)*) => ({
         // Expand the arguments so they don't cause unused warnings.
         if false {
-            let _ = ($($arg,)*);
+            let _ = ($(($arg) as isize,)*);
         }
         false
     })
@@ -1,5 +1,40 @@
-#[cfg(any(target_os = "linux", target_os = "android"))]
+// Each platform module defines the same two macros, with the same shapes, so
+// the frontend can target any of them:
+//
+// * `platform_probe!($(#[semaphore])? provider, name, args...,)` is a unit
+//   expression that always evaluates every argument once, in order.
+// * `platform_probe_lazy!($(#[export_name = "..."])? provider, name, args...,)`
+//   is a `bool` expression, true only if the probe ran and evaluated its
+//   arguments. An exported semaphore symbol is always defined.
+//
+// Each also defines `PLATFORM`, for `support::PLATFORM`. The lazy_contract
+// test compiles on every target in CI to keep the shapes in sync.
+
+#[cfg(all(any(target_os = "linux", target_os = "android"), not(probe_noop)))]
 mod systemtap;
+#[cfg(all(any(target_os = "linux", target_os = "android"), not(probe_noop)))]
+pub(crate) use self::systemtap::PLATFORM;
+
+#[cfg(all(target_os = "wasi", feature = "wasi-host", not(probe_noop)))]
+pub mod wasi;
+#[cfg(all(target_os = "wasi", feature = "wasi-host", not(probe_noop)))]
+pub(crate) use self::wasi::PLATFORM;
 
-#[cfg(not(any(target_os = "linux", target_os = "android")))]
+#[cfg(any(
+    not(any(
+        target_os = "linux",
+        target_os = "android",
+        all(target_os = "wasi", feature = "wasi-host"),
+    )),
+    probe_noop,
+))]
 mod default;
+#[cfg(any(
+    not(any(
+        target_os = "linux",
+        target_os = "android",
+        all(target_os = "wasi", feature = "wasi-host"),
+    )),
+    probe_noop,
+))]
+pub(crate) use self::default::PLATFORM;
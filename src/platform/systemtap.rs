@@ -46,32 +46,103 @@
 // to use positional `{}@{}` with a `const` operand for the size, but calling
 // things like `mem::size_of::<T>()` is still hard when we don't know `T`.
 //
+// The probe site itself is always the assembler's plain `nop`, which is the
+// smallest single-instruction NOP on each architecture (or `c.nop` on RISC-V
+// with compressed instructions). A probe without arguments has no asm inputs,
+// so the compiler has no registers to set up around it either, and the
+// verify tests check that by reading and disassembling such a site.
+//
+// The note only holds absolute address-sized words (`.4byte`/`.8byte`) for the
+// probe site, the base, and the semaphore. The note section is not allocated,
+// so these are resolved statically at link time and need no dynamic
+// relocations, PLT, or GOT entries. That keeps the template independent of the
+// relocation and code model, including `-C relocation-model=static` and
+// kernel-style builds, which CI exercises so it stays that way.
+//
+// The numeric labels 990-994 are GNU as local labels. They never reach the
+// object's symbol table, but they're scoped to the whole assembly file, not
+// to the asm block, so it's the references that keep them apart: a numeric
+// label may be defined any number of times, and every reference here is `b`
+// or `f`, to the nearest definition before or after it. Each block defines
+// every label it refers to, with nothing from another block in between, so
+// any number of probes expanded into one expression, or blocks duplicated by
+// inlining, each resolve to their own labels. (The numbers also avoid labels
+// made only of 0s and 1s, which x86 AT&T syntax could read as binary literals
+// like `0b`.)
+//
+// Notes are emitted in the order of the probes within each codegen unit, and
+// codegen units in the order that rustc links them, which is deterministic for
+// the same source and flags. The reproducible test builds a binary twice to
+// make sure the notes stay identical.
+//
+// The only symbol shared between probes is `_.stapsdt.base`, and it's defined
+// exactly like sys/sdt.h does: weak and hidden, in a COMDAT group named
+// `.stapsdt.base`. The linker keeps one copy of that group per module, no
+// matter how many crates, versions of this crate, or C objects emit it, so
+// every note in a binary agrees on the base. Semaphores are ordinary statics,
+// private to each probe site, whose mangled names include the crate's hash, so
+// separate versions never share or collide on them. The versions test links
+// two copies of this crate together to make sure that holds.
+//
+// With the `no-base` feature, notes record a zero base and nothing defines
+// `_.stapsdt.base`, which is how tools already read notes from binaries that
+// have no `.stapsdt.base` section: without prelink adjustment. That's only
+// correct if no other object in the link defines the section either, since a
+// tool would then adjust our zero base by its address. The version note clears
+// `Version::HAS_BASE` so consumers can tell which kind of notes they have.
+//
+// Every probe also emits the version note of this crate, guarded by `.ifndef`
+// so there's only one per object and version, and in a COMDAT group named
+// after the crate version so the linker keeps one per version. Its
+// descriptor is the emission format and flags as two 4-byte words, then the
+// version string, which the registry reads back as `registry::Version`. The
+// same group holds an ABI note, with the argument size, byte order (1 little,
+// 2 big, like ELF's EI_DATA), and argument encoding as 4-byte words, read as
+// `registry::Abi`.
+//
+// Items aren't hygienic in `macro_rules!`, so a semaphore static is visible to
+// the argument expressions in the same block. Since `sym` needs it in scope
+// next to those arguments, it gets a reserved name that user code won't have.
+//
 // FIXME semaphores - SDT can define a short* that debuggers will increment when
 // they attach, and decrement on detach. Thus a `probe_enabled!(provider,name)`
 // could return if that value != 0, to be used similarly to log_enabled!(). It
 // is difficult with mangling and macro hygene to connect two `probe!` and
 // `probe_enabled!` calls to the same symbol, unless we forced `#[no_mangle]`.
 // For now, we only use semaphores in `probe_lazy!` to skip argument evaluation
-// when there's nobody attached to see the probe.
+// when there's nobody attached to see the probe. A lazy probe may also give its
+// semaphore an `#[export_name]`, which is the one case where foreign code can
+// refer to the same flag. Eager probes may ask for a `#[semaphore]` as well,
+// but only so debuggers list one; the probe never reads it.
 //
 
+pub(crate) const PLATFORM: crate::support::Platform = crate::support::Platform::SystemTap;
+
 #[doc(hidden)]
 #[macro_export]
 macro_rules! platform_probe(
     ($provider:ident, $name:ident, $($arg:expr,)*) => ({
         $crate::sdt!([sym 0], $provider, $name, $($arg,)*);
-    })
+    });
+
+    // The semaphore is only for debuggers to see, so it's never checked.
+    (#[semaphore] $provider:ident, $name:ident, $($arg:expr,)*) => ({
+        #[link_section = ".probes"]
+        static __PROBE_SEMAPHORE: $crate::Semaphore = $crate::Semaphore::new();
+        $crate::sdt!([sym "{}" __PROBE_SEMAPHORE], $provider, $name, $($arg,)*);
+    });
 );
 
 #[doc(hidden)]
 #[macro_export]
 macro_rules! platform_probe_lazy(
-    ($provider:ident, $name:ident, $($arg:expr,)*) => ({
+    ($(#[export_name = $export:literal])? $provider:ident, $name:ident, $($arg:expr,)*) => ({
+        $(#[export_name = $export])?
         #[link_section = ".probes"]
-        static mut SEMAPHORE: u16 = 0;
-        let enabled = unsafe { ::core::ptr::read_volatile(&SEMAPHORE) } != 0;
+        static __PROBE_SEMAPHORE: $crate::Semaphore = $crate::Semaphore::new();
+        let enabled = __PROBE_SEMAPHORE.enabled();
         if enabled {
-            $crate::sdt!([sym "{}" SEMAPHORE], $provider, $name, $($arg,)*);
+            $crate::sdt!([sym "{}" __PROBE_SEMAPHORE], $provider, $name, $($arg,)*);
         }
         enabled
     })
@@ -109,7 +180,7 @@ macro_rules! sdt(
     ([sym $symstr:literal $($sym:ident)?, opt $($opt:ident)?, size $size:literal],
         $provider:ident, $name:ident, $($argstr:literal, $arg:expr,)*
     ) => (unsafe {
-        ::core::arch::asm!(concat!(r#"
+        ::core::arch::asm!(::core::concat!(r#"
 990:    nop
         .pushsection .note.stapsdt,"?","note"
         .balign 4
@@ -117,13 +188,63 @@ macro_rules! sdt(
 991:    .asciz "stapsdt"
 992:    .balign 4
 993:    ."#, $size, r#"byte 990b
-        ."#, $size, r#"byte _.stapsdt.base
+        ."#, $size, r#"byte "#, $crate::sdt_base!(word), r#"
         ."#, $size, r#"byte "#, $symstr, r#"
-        .asciz ""#, stringify!($provider), r#""
-        .asciz ""#, stringify!($name), r#""
+        .asciz ""#, ::core::stringify!($provider), r#""
+        .asciz ""#, ::core::stringify!($name), r#""
         .asciz ""#, $($argstr,)* r#""
 994:    .balign 4
         .popsection
+.ifndef ".Lrust_probe_version.v"#, $crate::probe_crate_version!(), r#""
+        .pushsection .note.rust-probe,"G","note",".rust-probe.v"#,
+            $crate::probe_crate_version!(), r#"",comdat
+        .set ".Lrust_probe_version.v"#, $crate::probe_crate_version!(), r#"", 1
+        .balign 4
+        .4byte 996f-995f, 998f-997f, 1
+995:    .asciz "rust-probe"
+996:    .balign 4
+997:    .4byte 1, "#, $crate::sdt_base!(flags), r#"
+        .asciz ""#, $crate::probe_crate_version!(), r#""
+998:    .balign 4
+        .4byte 996f-995f, 998f-997f, 2
+995:    .asciz "rust-probe"
+996:    .balign 4
+997:    .4byte "#, $size, r#", "#, $crate::sdt_endian!(), r#", 1
+998:    .balign 4
+        .popsection
+.endif
+"#, $crate::sdt_base!(section)),
+            $(sym $sym,)?
+            $(in(reg) ($arg) as isize,)*
+            options(readonly, nostack, preserves_flags $(, $opt)?),
+        )
+    });
+);
+
+// The byte order for the ABI note, chosen here like `sdt_base!` below.
+#[cfg(target_endian = "little")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! sdt_endian(
+    () => ("1");
+);
+
+#[cfg(target_endian = "big")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! sdt_endian(
+    () => ("2");
+);
+
+// The parts of the note template that refer to `_.stapsdt.base`, chosen here
+// since a `cfg` in the expansion would check the caller's features instead.
+#[cfg(not(feature = "no-base"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! sdt_base(
+    (word) => ("_.stapsdt.base");
+    (flags) => ("1");
+    (section) => (r#"
 .ifndef _.stapsdt.base
         .pushsection .stapsdt.base,"aG","progbits",.stapsdt.base,comdat
         .weak _.stapsdt.base
@@ -131,10 +252,14 @@ macro_rules! sdt(
 _.stapsdt.base: .space 1
         .size _.stapsdt.base, 1
         .popsection
-.endif"#),
-            $(sym $sym,)?
-            $(in(reg) ($arg) as isize,)*
-            options(readonly, nostack, preserves_flags $(, $opt)?),
-        )
-    });
+.endif"#);
+);
+
+#[cfg(feature = "no-base")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! sdt_base(
+    (word) => ("0");
+    (flags) => ("0");
+    (section) => ("");
 );
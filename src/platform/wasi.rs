@@ -0,0 +1,150 @@
+//! WASI host-imported probes
+//!
+//! WebAssembly modules have no ELF notes and no `NOP` patching, so on WASI
+//! targets probes are forwarded to the embedding runtime instead. With the
+//! `wasi-host` feature enabled, each probe calls functions imported from the
+//! `probe` module, which the host must provide:
+//!
+//! ```notrust
+//! (import "probe" "enabled" (func (param i32 i32 i32 i32) (result i32)))
+//! (import "probe" "fire" (func (param i32 i32 i32 i32 i32 i32)))
+//! ```
+//!
+//! The parameters are pointer/length pairs for the provider and name strings,
+//! followed by a pointer/count for an array of `i64` arguments in linear
+//! memory. `enabled` is only consulted by `probe_lazy!`, and should return
+//! non-zero when the host wants to see that probe's arguments.
+//!
+//! Each probe site also adds a record to the `probe.sites` custom section, so
+//! runtimes and devtools can list a module's probes without running it. Records are concatenated, each with a format
+//! byte (currently 1), a flags byte (bit 0 set for `probe_lazy!`), the number
+//! of arguments, then the provider and name as NUL-terminated strings.
+//! `registry::wasi_sites` parses them. Unlike SDT notes, the records don't
+//! locate their sites: function indices are only assigned by `wasm-ld`, after
+//! the records are fixed. Hosts find the sites themselves as the callers of
+//! the `probe` imports, which is also where they can trap.
+//!
+//! Without the feature, WASI targets fall back to the default backend, so
+//! modules don't require any imports that a runtime might not satisfy.
+
+pub(crate) const PLATFORM: crate::support::Platform = crate::support::Platform::WasiHost;
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! platform_probe(
+    ($provider:ident, $name:ident, $($arg:expr,)*) => ({
+        $crate::wasi_probe_site!(0, $provider, $name, $($arg,)*);
+        $crate::wasi_probe_fire!($provider, $name, $($arg,)*);
+    });
+
+    (#[semaphore] $provider:ident, $name:ident, $($arg:expr,)*)
+    => ($crate::platform_probe!($provider, $name, $($arg,)*));
+);
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! platform_probe_lazy(
+    (#[export_name = $export:literal] $provider:ident, $name:ident, $($arg:expr,)*) => ({
+        // The host decides enablement, so the exported semaphore is never set.
+        {
+            #[export_name = $export]
+            static SEMAPHORE: $crate::Semaphore = $crate::Semaphore::new();
+        }
+        $crate::platform_probe_lazy!($provider, $name, $($arg,)*)
+    });
+
+    ($provider:ident, $name:ident, $($arg:expr,)*) => ({
+        $crate::wasi_probe_site!($crate::platform::wasi::LAZY, $provider, $name, $($arg,)*);
+        let enabled = $crate::platform::wasi::enabled(::core::stringify!($provider), ::core::stringify!($name));
+        if enabled {
+            $crate::wasi_probe_fire!($provider, $name, $($arg,)*);
+        }
+        enabled
+    })
+);
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! wasi_probe_fire(
+    ($provider:ident, $name:ident, $($arg:expr,)*) => ({
+        let args: &[i64] = &[$(($arg) as isize as i64,)*];
+        $crate::platform::wasi::fire(::core::stringify!($provider), ::core::stringify!($name), args);
+    })
+);
+
+#[doc(hidden)]
+#[macro_export]
+macro_rules! wasi_probe_site(
+    ($flags:expr, $provider:ident, $name:ident, $($arg:expr,)*) => ({
+        const ARGS: usize = <[&str]>::len(&[$(::core::stringify!($arg)),*]);
+        const TEXT: &str = ::core::concat!(
+            ::core::stringify!($provider), "\0",
+            ::core::stringify!($name), "\0",
+        );
+        // Custom sections can't hold relocations, so the strings are inline.
+        #[link_section = "probe.sites"]
+        #[used]
+        static SITE: [u8; 3 + TEXT.len()] = $crate::platform::wasi::site($flags, ARGS, TEXT);
+    })
+);
+
+/// The record format of the `probe.sites` section.
+const FORMAT: u8 = 1;
+
+/// The record flag of lazy sites.
+pub const LAZY: u8 = 1;
+
+/// Build the `probe.sites` record of one probe site.
+pub const fn site<const N: usize>(flags: u8, args: usize, text: &str) -> [u8; N] {
+    let text = text.as_bytes();
+    let mut record = [0; N];
+    record[0] = FORMAT;
+    record[1] = flags;
+    record[2] = args as u8;
+    let mut i = 0;
+    while i < text.len() {
+        record[3 + i] = text[i];
+        i += 1;
+    }
+    record
+}
+
+mod host {
+    #[link(wasm_import_module = "probe")]
+    extern "C" {
+        pub fn enabled(
+            provider: *const u8,
+            provider_len: usize,
+            name: *const u8,
+            name_len: usize,
+        ) -> u32;
+
+        pub fn fire(
+            provider: *const u8,
+            provider_len: usize,
+            name: *const u8,
+            name_len: usize,
+            args: *const i64,
+            nargs: usize,
+        );
+    }
+}
+
+#[inline]
+pub fn enabled(provider: &str, name: &str) -> bool {
+    unsafe { host::enabled(provider.as_ptr(), provider.len(), name.as_ptr(), name.len()) != 0 }
+}
+
+#[inline]
+pub fn fire(provider: &str, name: &str, args: &[i64]) {
+    unsafe {
+        host::fire(
+            provider.as_ptr(),
+            provider.len(),
+            name.as_ptr(),
+            name.len(),
+            args.as_ptr(),
+            args.len(),
+        )
+    }
+}
@@ -0,0 +1,71 @@
+//! Previewing the SDT argument encoding of a probe.
+//!
+//! The SystemTap backend describes each argument in its note with a string
+//! like `-8@%rax`: the size in bytes, negative for signed values, then the
+//! operand that holds it. The operand is only chosen by the register
+//! allocator, but everything else is fixed by the probe's arguments, so it
+//! can be checked in unit tests without building and inspecting a binary.
+//! See [`probe_preview!`](crate::probe_preview).
+
+use core::fmt;
+
+/// The argument encoding that a probe would emit.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Preview {
+    /// The probe's provider.
+    pub provider: &'static str,
+    /// The probe's name.
+    pub name: &'static str,
+    /// The number of arguments.
+    pub args: usize,
+}
+
+impl Preview {
+    /// Returns the size of each argument, negative for signed values.
+    ///
+    /// Every argument is cast `as isize`, so this is the signed pointer size.
+    pub const fn arg_size(&self) -> isize {
+        -(core::mem::size_of::<isize>() as isize)
+    }
+
+    /// Returns the class of each argument operand.
+    ///
+    /// Every argument is passed in a general-purpose register.
+    pub const fn operand_class(&self) -> &'static str {
+        "reg"
+    }
+
+    /// Returns `true` if `argstr` from an actual note matches this preview.
+    ///
+    /// This compares the number and sizes of the arguments, and accepts any
+    /// register operand, since those can change with every build.
+    pub fn matches(&self, argstr: &str) -> bool {
+        let mut count = 0;
+        for arg in argstr.split_whitespace() {
+            match arg.split_once('@') {
+                Some((size, operand)) if size.parse() == Ok(self.arg_size()) => {
+                    if operand.is_empty() {
+                        return false;
+                    }
+                }
+                _ => return false,
+            }
+            count += 1;
+        }
+        count == self.args
+    }
+}
+
+impl fmt::Display for Preview {
+    /// Formats the argument string with placeholder operands, like
+    /// `-8@%reg -8@%reg`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for i in 0..self.args {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{}@%{}", self.arg_size(), self.operand_class())?;
+        }
+        Ok(())
+    }
+}
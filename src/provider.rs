@@ -0,0 +1,213 @@
+//! Stable provider identities
+//!
+//! Some tracing systems identify providers by number rather than by name, like
+//! the GUIDs of Windows ETW. Since `probe!` providers are only identifiers,
+//! this derives those numbers from the provider name in a documented, stable
+//! way, so collectors can be configured before a binary is ever deployed.
+//!
+//! The derivation is the same one used by .NET `EventSource` and TraceLogging
+//! for name-based provider GUIDs, so existing ETW tooling will agree with it:
+//!
+//! 1. Convert the name to upper case. Only ASCII letters are affected, which
+//!    covers any provider written as a Rust identifier in practice.
+//! 2. Hash the 16-byte namespace `482C2DB2-C390-47C8-87F8-1A15BFC130FB`,
+//!    followed by the name encoded as big-endian UTF-16, with SHA-1.
+//! 3. Take the first 16 bytes of the hash as a little-endian GUID, and set
+//!    its version nibble to 5.
+
+use core::fmt;
+
+/// A 128-bit provider GUID, as used by ETW.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Guid {
+    /// The first 32 bits of the GUID.
+    pub data1: u32,
+    /// The next 16 bits of the GUID.
+    pub data2: u16,
+    /// The next 16 bits of the GUID.
+    pub data3: u16,
+    /// The last 64 bits of the GUID, in byte order.
+    pub data4: [u8; 8],
+}
+
+const NAMESPACE: [u8; 16] = [
+    0x48, 0x2C, 0x2D, 0xB2, 0xC3, 0x90, 0x47, 0xC8, //
+    0x87, 0xF8, 0x1A, 0x15, 0xBF, 0xC1, 0x30, 0xFB,
+];
+
+impl Guid {
+    /// Derive the GUID for a provider name.
+    ///
+    /// This is a `const fn`, so it can be evaluated at compile time. The
+    /// [`provider_guid!`](crate::provider_guid) macro does the same for a
+    /// provider identifier.
+    pub const fn from_name(name: &str) -> Guid {
+        let mut sha = Sha1::new();
+
+        let mut i = 0;
+        while i < NAMESPACE.len() {
+            sha = sha.update(NAMESPACE[i]);
+            i += 1;
+        }
+
+        let bytes = name.as_bytes();
+        let mut i = 0;
+        while i < bytes.len() {
+            // Decode one UTF-8 sequence, which `&str` guarantees is valid.
+            let b = bytes[i] as u32;
+            let (mut c, len) = if b < 0x80 {
+                (b, 1)
+            } else if b < 0xE0 {
+                (b & 0x1F, 2)
+            } else if b < 0xF0 {
+                (b & 0x0F, 3)
+            } else {
+                (b & 0x07, 4)
+            };
+            let mut j = 1;
+            while j < len {
+                c = (c << 6) | (bytes[i + j] as u32 & 0x3F);
+                j += 1;
+            }
+            i += len;
+
+            if c >= 'a' as u32 && c <= 'z' as u32 {
+                c -= 'a' as u32 - 'A' as u32;
+            }
+
+            // Encode it as big-endian UTF-16.
+            if c < 0x10000 {
+                sha = sha.update((c >> 8) as u8).update(c as u8);
+            } else {
+                let c = c - 0x10000;
+                let high = 0xD800 | (c >> 10);
+                let low = 0xDC00 | (c & 0x3FF);
+                sha = sha.update((high >> 8) as u8).update(high as u8);
+                sha = sha.update((low >> 8) as u8).update(low as u8);
+            }
+        }
+
+        let hash = sha.finish();
+        Guid {
+            data1: u32::from_le_bytes([hash[0], hash[1], hash[2], hash[3]]),
+            data2: u16::from_le_bytes([hash[4], hash[5]]),
+            data3: u16::from_le_bytes([hash[6], (hash[7] & 0x0F) | 0x50]),
+            data4: [
+                hash[8], hash[9], hash[10], hash[11], hash[12], hash[13], hash[14], hash[15],
+            ],
+        }
+    }
+}
+
+impl fmt::Display for Guid {
+    /// Formats the GUID in its usual hyphenated form, without braces.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let d = &self.data4;
+        write!(
+            f,
+            "{:08x}-{:04x}-{:04x}-{:02x}{:02x}-{:02x}{:02x}{:02x}{:02x}{:02x}{:02x}",
+            self.data1, self.data2, self.data3, d[0], d[1], d[2], d[3], d[4], d[5], d[6], d[7],
+        )
+    }
+}
+
+/// A minimal SHA-1 that can run in `const fn`, one byte at a time.
+struct Sha1 {
+    state: [u32; 5],
+    block: [u8; 64],
+    len: usize,
+    total: u64,
+}
+
+impl Sha1 {
+    const fn new() -> Sha1 {
+        Sha1 {
+            state: [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0],
+            block: [0; 64],
+            len: 0,
+            total: 0,
+        }
+    }
+
+    const fn update(mut self, byte: u8) -> Sha1 {
+        self.block[self.len] = byte;
+        self.len += 1;
+        self.total += 1;
+        if self.len == self.block.len() {
+            self.state = sha1_compress(self.state, &self.block);
+            self.len = 0;
+        }
+        self
+    }
+
+    const fn finish(mut self) -> [u8; 20] {
+        let bits = self.total * 8;
+        self = self.update(0x80);
+        while self.len != 56 {
+            self = self.update(0);
+        }
+        let len = bits.to_be_bytes();
+        let mut i = 0;
+        while i < len.len() {
+            self = self.update(len[i]);
+            i += 1;
+        }
+
+        let mut hash = [0; 20];
+        let mut i = 0;
+        while i < hash.len() {
+            hash[i] = (self.state[i / 4] >> (24 - 8 * (i % 4))) as u8;
+            i += 1;
+        }
+        hash
+    }
+}
+
+const fn sha1_compress(state: [u32; 5], block: &[u8; 64]) -> [u32; 5] {
+    let mut w = [0u32; 80];
+    let mut i = 0;
+    while i < 16 {
+        w[i] = u32::from_be_bytes([
+            block[4 * i],
+            block[4 * i + 1],
+            block[4 * i + 2],
+            block[4 * i + 3],
+        ]);
+        i += 1;
+    }
+    while i < 80 {
+        w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        i += 1;
+    }
+
+    let [mut a, mut b, mut c, mut d, mut e] = state;
+    let mut i = 0;
+    while i < 80 {
+        let (f, k) = match i / 20 {
+            0 => ((b & c) | (!b & d), 0x5A827999),
+            1 => (b ^ c ^ d, 0x6ED9EBA1),
+            2 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+            _ => (b ^ c ^ d, 0xCA62C1D6),
+        };
+        let temp = a
+            .rotate_left(5)
+            .wrapping_add(f)
+            .wrapping_add(e)
+            .wrapping_add(k)
+            .wrapping_add(w[i]);
+        e = d;
+        d = c;
+        c = b.rotate_left(30);
+        b = a;
+        a = temp;
+        i += 1;
+    }
+
+    [
+        state[0].wrapping_add(a),
+        state[1].wrapping_add(b),
+        state[2].wrapping_add(c),
+        state[3].wrapping_add(d),
+        state[4].wrapping_add(e),
+    ]
+}
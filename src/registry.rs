@@ -0,0 +1,1013 @@
+//! Reading probe metadata back from ELF binaries.
+//!
+//! This parses the SystemTap SDT notes that `probe!` emits on Linux, the same
+//! way that SystemTap, GDB, and other consumers find them. It works on any ELF
+//! file from any host, so it can check binaries after they've been stripped,
+//! packaged, or built for another architecture.
+//!
+//! [`wasi_sites`] reads the `probe.sites` custom section that the `wasi-host`
+//! feature adds to WASI modules. Unlike SDT notes, its records don't locate
+//! the sites in the code.
+//!
+//! On Linux, [`current()`] and [`find()`] index the probes of the running
+//! executable at their runtime addresses, for tools that work in-process.
+//!
+//! This module requires the `std` feature.
+
+use std::collections::{HashMap, HashSet};
+use std::error;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+use std::string::String;
+use std::vec::Vec;
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use crate::Semaphore;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::{ptr, sync::Once};
+
+/// The note type used for SDT probe descriptors.
+const NT_STAPSDT: u32 = 3;
+
+/// The section type for ELF notes.
+const SHT_NOTE: u32 = 7;
+
+/// The note type of this crate's version note.
+const NT_RUST_PROBE_VERSION: u32 = 1;
+const NT_RUST_PROBE_ABI: u32 = 2;
+
+/// A probe described by an SDT note.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Probe {
+    /// The probe's provider.
+    pub provider: String,
+    /// The probe's name.
+    pub name: String,
+    /// The argument descriptions, like `-8@%rax -8@%rdx`.
+    pub arguments: String,
+    /// The link-time address of the probe site.
+    pub location: u64,
+    /// The link-time address of `.stapsdt.base`, or zero if it's absent.
+    pub base: u64,
+    /// The link-time address of the probe's semaphore, if it has one.
+    pub semaphore: Option<u64>,
+}
+
+impl fmt::Display for Probe {
+    /// Formats the probe as one manifest line, like
+    /// `foo:bar location=0x401234 semaphore=0x404010 args="-8@%rax"`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{} location={:#x}",
+            self.provider, self.name, self.location
+        )?;
+        if let Some(semaphore) = self.semaphore {
+            write!(f, " semaphore={:#x}", semaphore)?;
+        }
+        write!(f, " args={:?}", self.arguments)
+    }
+}
+
+/// The version of this crate that emitted probes into a binary.
+///
+/// Besides its SDT notes, each version of this crate that emits any probe into
+/// a binary adds one `.note.rust-probe` note, owned by `rust-probe`, holding
+/// the emission format, flags, and crate version. Consumers can use it to
+/// adapt as the way notes are emitted evolves.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Version {
+    /// The version of this crate, like `0.5.1`.
+    pub version: String,
+    /// The emission format, currently always 1.
+    pub format: u32,
+    /// Flags describing the emitted notes, like [`Version::HAS_BASE`].
+    pub flags: u32,
+}
+
+impl Version {
+    /// The notes refer to a `.stapsdt.base` symbol. Without it, as with the
+    /// `no-base` feature, their base is zero and they need no adjustment.
+    pub const HAS_BASE: u32 = 1;
+}
+
+/// How the probes of one version of this crate encode their arguments.
+///
+/// Next to its [`Version`] note, each version of this crate adds a note
+/// describing the argument ABI of the target, so generic consumers can decode
+/// arguments from any binary without per-target assumptions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Abi {
+    /// The size in bytes of each argument and address in the notes.
+    pub pointer_width: u32,
+    /// Whether values are big-endian.
+    pub big_endian: bool,
+    /// The encoding of arguments, currently always [`Abi::SIGNED_WORDS`].
+    pub arguments: u32,
+}
+
+impl Abi {
+    /// Each argument is a signed integer of `pointer_width` bytes, located
+    /// by its `-N@operand` description in the SDT note.
+    pub const SIGNED_WORDS: u32 = 1;
+}
+
+/// A probe site described by the `probe.sites` section of a WASI module.
+///
+/// There's no function index or code offset, so this only says that a site
+/// exists. Hosts find where it is as a caller of the `probe` imports.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WasiSite {
+    /// The probe's provider.
+    pub provider: String,
+    /// The probe's name.
+    pub name: String,
+    /// The number of arguments the site passes to the host's `fire`.
+    pub arguments: u8,
+    /// Whether the site is lazy, and asks the host's `enabled` first.
+    pub lazy: bool,
+}
+
+/// An error reading probes from a binary.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The data is not an ELF file, or is truncated or malformed.
+    Malformed(&'static str),
+    /// The binary doesn't contain any SDT notes.
+    NoProbes,
+    /// A probe's semaphore is not in the `.probes` section, so debuggers
+    /// would not be able to find and enable it.
+    BadSemaphore(Probe),
+    /// No site of the named probe has a semaphore to enable.
+    NoSemaphore(String),
+    /// The binary's probes exceed a [`Budget`], with a report of the usage.
+    OverBudget(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(err) => write!(f, "failed to read binary: {}", err),
+            Error::Malformed(what) => write!(f, "malformed ELF file: {}", what),
+            Error::NoProbes => f.write_str("no SDT notes found in .note.stapsdt"),
+            Error::BadSemaphore(probe) => write!(
+                f,
+                "semaphore for {}:{} is outside the .probes section",
+                probe.provider, probe.name
+            ),
+            Error::NoSemaphore(probe) => write!(f, "no semaphore found for {}", probe),
+            Error::OverBudget(report) => write!(f, "probe budget exceeded: {}", report),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+/// Read all probes from the ELF file at `path`.
+pub fn read(path: impl AsRef<Path>) -> Result<Vec<Probe>, Error> {
+    parse(&fs::read(path)?)
+}
+
+/// Parse all probes from the contents of an ELF file.
+pub fn parse(data: &[u8]) -> Result<Vec<Probe>, Error> {
+    let elf = Elf::new(data)?;
+    let mut probes = Vec::new();
+    for section in elf.sections()? {
+        if section.kind == SHT_NOTE && section.name == b".note.stapsdt" {
+            elf.parse_notes(&section, &mut probes)?;
+        }
+    }
+    Ok(probes)
+}
+
+/// Parse the versions of this crate that emitted probes, from the contents of
+/// an ELF file.
+///
+/// This is empty for binaries built before the version note was introduced,
+/// or with probes from other emitters only, like `<sys/sdt.h>`.
+pub fn versions(data: &[u8]) -> Result<Vec<Version>, Error> {
+    let elf = Elf::new(data)?;
+    let mut versions = Vec::new();
+    for section in elf.sections()? {
+        if section.kind == SHT_NOTE && section.name == b".note.rust-probe" {
+            for (kind, name, desc) in elf.notes(&section)? {
+                if kind == NT_RUST_PROBE_VERSION && name == b"rust-probe\0" {
+                    versions.push(elf.parse_version(desc)?);
+                }
+            }
+        }
+    }
+    Ok(versions)
+}
+
+/// Parse the argument ABIs of the versions of this crate that emitted probes,
+/// from the contents of an ELF file.
+///
+/// Like [`versions`], this is empty for binaries from before the ABI note.
+pub fn abis(data: &[u8]) -> Result<Vec<Abi>, Error> {
+    let elf = Elf::new(data)?;
+    let mut abis = Vec::new();
+    for section in elf.sections()? {
+        if section.kind == SHT_NOTE && section.name == b".note.rust-probe" {
+            for (kind, name, desc) in elf.notes(&section)? {
+                if kind == NT_RUST_PROBE_ABI && name == b"rust-probe\0" {
+                    abis.push(elf.parse_abi(desc)?);
+                }
+            }
+        }
+    }
+    Ok(abis)
+}
+
+/// Parse the probe sites recorded in a WASI module.
+///
+/// Every custom section named `probe.sites` is read, in order, so this is
+/// empty for modules without probes, or built without the `wasi-host`
+/// feature.
+pub fn wasi_sites(data: &[u8]) -> Result<Vec<WasiSite>, Error> {
+    let mut data = match data.strip_prefix(b"\0asm\x01\0\0\0") {
+        Some(data) => data,
+        None => return Err(Error::Malformed("missing WebAssembly header")),
+    };
+    let mut sites = Vec::new();
+    while let Some((&id, rest)) = data.split_first() {
+        let (size, rest) = leb128(rest)?;
+        if rest.len() < size {
+            return Err(Error::Malformed("truncated section"));
+        }
+        let (section, rest) = rest.split_at(size);
+        data = rest;
+        if id != 0 {
+            continue;
+        }
+        let (len, section) = leb128(section)?;
+        if section.len() < len {
+            return Err(Error::Malformed("truncated section name"));
+        }
+        let (name, mut records) = section.split_at(len);
+        if name != b"probe.sites" {
+            continue;
+        }
+        while !records.is_empty() {
+            let (site, rest) = parse_wasi_site(records)?;
+            sites.push(site);
+            records = rest;
+        }
+    }
+    Ok(sites)
+}
+
+fn parse_wasi_site(data: &[u8]) -> Result<(WasiSite, &[u8]), Error> {
+    let (flags, arguments, rest) = match *data {
+        [1, flags, arguments, ref rest @ ..] => (flags, arguments, rest),
+        [_, _, _, ..] => return Err(Error::Malformed("unknown site format")),
+        _ => return Err(Error::Malformed("truncated site record")),
+    };
+    let (provider, rest) = cstr(rest)?;
+    let (name, rest) = cstr(rest)?;
+    let site = WasiSite {
+        provider: String::from_utf8_lossy(provider).into_owned(),
+        name: String::from_utf8_lossy(name).into_owned(),
+        arguments,
+        lazy: flags & 1 != 0,
+    };
+    Ok((site, rest))
+}
+
+/// Split an unsigned LEB128 number from the front of `data`.
+fn leb128(data: &[u8]) -> Result<(usize, &[u8]), Error> {
+    let mut n: usize = 0;
+    for (i, &byte) in data.iter().enumerate().take(5) {
+        n |= usize::from(byte & 0x7f) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Ok((n, &data[i + 1..]));
+        }
+    }
+    Err(Error::Malformed("bad LEB128 number"))
+}
+
+/// Check that the ELF file at `path` still carries usable probes.
+///
+/// This is meant to run after linking, stripping, and packaging, to catch
+/// steps that removed the `.note.stapsdt` section. It returns the probes found
+/// if there is at least one, and every semaphore is in the `.probes` section.
+///
+/// The notes are not allocated, like those of `<sys/sdt.h>`, so they can use
+/// link-time addresses without dynamic relocations. `strip` and
+/// `strip --strip-debug` keep them, and `-C split-debuginfo=packed` leaves
+/// them in the binary rather than moving them to the debuginfo package, but
+/// scripts that remove all non-allocated sections will lose them.
+pub fn verify_binary(path: impl AsRef<Path>) -> Result<Vec<Probe>, Error> {
+    let data = fs::read(path)?;
+    let probes = parse(&data)?;
+    if probes.is_empty() {
+        return Err(Error::NoProbes);
+    }
+
+    let semaphores = Elf::new(&data)?.probes_section()?;
+    for probe in &probes {
+        if let Some(semaphore) = probe.semaphore {
+            if !in_section(&semaphores, semaphore) {
+                return Err(Error::BadSemaphore(probe.clone()));
+            }
+        }
+    }
+    Ok(probes)
+}
+
+/// Limits on the probes in a binary, for [`check_budget`].
+///
+/// Each limit is unchecked when `None`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Budget {
+    /// The most probe sites, counting each inlined or monomorphized copy.
+    pub max_sites: Option<usize>,
+    /// The most bytes of `.note.stapsdt` notes.
+    pub max_note_bytes: Option<u64>,
+}
+
+/// The probes in a binary, as counted against a [`Budget`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Usage {
+    /// The number of probe sites.
+    pub sites: usize,
+    /// The size of the `.note.stapsdt` notes in bytes.
+    pub note_bytes: u64,
+}
+
+/// Check the probes in the ELF file at `path` against a `budget`.
+///
+/// Cargo has no step after linking, so this is meant to run from a test or a
+/// CI script, like [`verify_binary`], to fail the build when instrumentation
+/// grows beyond an agreed budget. On success, it returns the usage, which may
+/// be worth logging to see the trend. Otherwise the error reports the usage,
+/// the limits, and the probes with the most sites.
+///
+/// # Example
+///
+/// ```no_run
+/// use probe::registry::{check_budget, Budget};
+///
+/// let budget = Budget {
+///     max_sites: Some(500),
+///     max_note_bytes: Some(64 * 1024),
+/// };
+/// let exe = std::env::current_exe().unwrap();
+/// if let Err(err) = check_budget(exe, &budget) {
+///     panic!("{}", err);
+/// }
+/// ```
+pub fn check_budget(path: impl AsRef<Path>, budget: &Budget) -> Result<Usage, Error> {
+    let data = fs::read(path)?;
+    // Parsing first checks that each note section is within the file.
+    let registry = Registry::new(parse(&data)?);
+    let elf = Elf::new(&data)?;
+    let mut note_bytes = 0;
+    for section in elf.sections()? {
+        if section.kind == SHT_NOTE && section.name == b".note.stapsdt" {
+            note_bytes += section.size;
+        }
+    }
+    let usage = Usage {
+        sites: registry.probes().len(),
+        note_bytes,
+    };
+
+    let sites_over = budget.max_sites.map_or(false, |max| usage.sites > max);
+    let bytes_over = budget
+        .max_note_bytes
+        .map_or(false, |max| usage.note_bytes > max);
+    if !sites_over && !bytes_over {
+        return Ok(usage);
+    }
+
+    // Writing to a `String` can't fail.
+    let mut report = String::new();
+    let _ = write!(report, "{} sites", usage.sites);
+    if let Some(max) = budget.max_sites {
+        let _ = write!(report, " (max {})", max);
+    }
+    let _ = write!(report, ", {} note bytes", usage.note_bytes);
+    if let Some(max) = budget.max_note_bytes {
+        let _ = write!(report, " (max {})", max);
+    }
+    report.push_str("; most sites:");
+    for (provider, name, count) in registry.site_counts().into_iter().take(5) {
+        let _ = write!(report, " {}:{} ({})", provider, name, count);
+    }
+    Err(Error::OverBudget(report))
+}
+
+/// The bytes that one provider's probes add to a binary, from [`sizes`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Size {
+    /// The provider.
+    pub provider: String,
+    /// The number of probe sites.
+    pub sites: usize,
+    /// The size of the provider's `.note.stapsdt` notes, including headers
+    /// and padding.
+    pub note_bytes: u64,
+    /// The size of the provider's semaphores in `.probes`.
+    pub semaphore_bytes: u64,
+}
+
+/// Report the bytes that each provider's probes add to an ELF file, largest
+/// first.
+///
+/// This covers the notes and semaphores, which are all a probe adds outside
+/// the code. A probe's site itself is a single `nop`, but setting up its
+/// arguments is interleaved with the optimized code around it, so it can't be
+/// measured from the binary. Compare builds with and without the probes, as
+/// with the `noop` feature, to see that. Notes aren't loaded at runtime, so
+/// only semaphores take up memory.
+pub fn sizes(data: &[u8]) -> Result<Vec<Size>, Error> {
+    let elf = Elf::new(data)?;
+    let mut sizes: Vec<Size> = Vec::new();
+    let mut semaphores = HashSet::new();
+    for section in elf.sections()? {
+        if section.kind != SHT_NOTE || section.name != b".note.stapsdt" {
+            continue;
+        }
+        for (kind, name, desc) in elf.notes(&section)? {
+            if kind != NT_STAPSDT || name != b"stapsdt\0" {
+                continue;
+            }
+            let probe = elf.parse_probe(desc)?;
+            let bytes = 12 + align4(name.len() as u64) + align4(desc.len() as u64);
+            let i = match sizes.iter().position(|s| s.provider == probe.provider) {
+                Some(i) => i,
+                None => {
+                    sizes.push(Size {
+                        provider: probe.provider.clone(),
+                        sites: 0,
+                        note_bytes: 0,
+                        semaphore_bytes: 0,
+                    });
+                    sizes.len() - 1
+                }
+            };
+            sizes[i].sites += 1;
+            sizes[i].note_bytes += bytes;
+            // Sites can share a semaphore, like the `<sys/sdt.h>` ones.
+            if let Some(semaphore) = probe.semaphore {
+                if semaphores.insert(semaphore) {
+                    sizes[i].semaphore_bytes += 2;
+                }
+            }
+        }
+    }
+    sizes.sort_by(|a, b| {
+        let total = |s: &Size| s.note_bytes + s.semaphore_bytes;
+        total(b)
+            .cmp(&total(a))
+            .then_with(|| a.provider.cmp(&b.provider))
+    });
+    Ok(sizes)
+}
+
+/// An index of probes by provider and name.
+#[derive(Clone, Debug, Default)]
+pub struct Registry {
+    // Sorted by provider and name, so each pair is a contiguous range.
+    probes: Vec<Probe>,
+    index: HashMap<String, HashMap<String, Range<usize>>>,
+}
+
+impl Registry {
+    /// Build an index of the given probes.
+    pub fn new(mut probes: Vec<Probe>) -> Registry {
+        probes.sort_by(|a, b| (&a.provider, &a.name).cmp(&(&b.provider, &b.name)));
+        let mut index: HashMap<String, HashMap<String, Range<usize>>> = HashMap::new();
+        let mut start = 0;
+        for (i, probe) in probes.iter().enumerate() {
+            let next = probes.get(i + 1);
+            if next.map_or(true, |p| {
+                p.provider != probe.provider || p.name != probe.name
+            }) {
+                index
+                    .entry(probe.provider.clone())
+                    .or_default()
+                    .insert(probe.name.clone(), start..i + 1);
+                start = i + 1;
+            }
+        }
+        Registry { probes, index }
+    }
+
+    /// Returns all probes, sorted by provider and name.
+    pub fn probes(&self) -> &[Probe] {
+        &self.probes
+    }
+
+    /// Returns the semaphore address of every probe site that has one.
+    ///
+    /// Agents that enable probes by writing to another process's memory need
+    /// these addresses, adjusted by that process's load bias, and must
+    /// increment every semaphore of a probe, as each lazy site has its own.
+    pub fn semaphores(&self) -> impl Iterator<Item = (&Probe, u64)> + '_ {
+        let probes = self.probes.iter();
+        probes.filter_map(|probe| Some((probe, probe.semaphore?)))
+    }
+
+    /// Returns every site of the probe with the given provider and name.
+    ///
+    /// A single probe may have many sites, for instance after inlining.
+    pub fn find(&self, provider: &str, name: &str) -> &[Probe] {
+        match self.index.get(provider).and_then(|names| names.get(name)) {
+            Some(range) => &self.probes[range.clone()],
+            None => &[],
+        }
+    }
+
+    /// Returns how many sites each probe expanded to, most first.
+    ///
+    /// Inlining and monomorphization copy a probe into every place its code
+    /// ends up, so this is a quick way to spot probes that exploded into
+    /// hundreds of sites. Ties are ordered by provider and name.
+    pub fn site_counts(&self) -> Vec<(&str, &str, usize)> {
+        let mut counts: Vec<_> = self
+            .index
+            .iter()
+            .flat_map(|(provider, names)| {
+                names
+                    .iter()
+                    .map(move |(name, range)| (&**provider, &**name, range.len()))
+            })
+            .collect();
+        counts.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| (a.0, a.1).cmp(&(b.0, b.1))));
+        counts
+    }
+}
+
+/// Returns the registry of probes in the current process's executable.
+///
+/// This is read from `/proc/self/exe` on first use. Each probe's `location`
+/// and `semaphore` are already adjusted to their runtime addresses in this
+/// process, accounting for PIE and ASLR, so they can be used directly.
+/// Probes in shared libraries are not included.
+///
+/// Semaphores outside the `.probes` section, where [`verify_binary`] would
+/// fail, are left out, so every semaphore reported is a 16-bit counter in
+/// that section.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn current() -> Result<&'static Registry, &'static Error> {
+    static ONCE: Once = Once::new();
+    static mut CURRENT: Option<Result<Registry, Error>> = None;
+    unsafe {
+        ONCE.call_once(|| *ptr::addr_of_mut!(CURRENT) = Some(load_current()));
+        match &*ptr::addr_of!(CURRENT) {
+            Some(result) => result.as_ref(),
+            None => unreachable!(),
+        }
+    }
+}
+
+/// Returns the sites of a probe in the current process, at runtime addresses.
+///
+/// This is a shortcut for [`current()`] and [`Registry::find`], and returns
+/// nothing if the executable couldn't be read.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn find(provider: &str, name: &str) -> &'static [Probe] {
+    match current() {
+        Ok(registry) => registry.find(provider, name),
+        Err(_) => &[],
+    }
+}
+
+/// Returns the semaphores of a probe's lazy sites in the current process.
+///
+/// Sites that share a semaphore after inlining only contribute it once.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub(crate) fn semaphores(provider: &str, name: &str) -> Vec<&'static Semaphore> {
+    let sites = find(provider, name).iter();
+    let mut addresses: Vec<u64> = sites.filter_map(|probe| probe.semaphore).collect();
+    addresses.sort_unstable();
+    addresses.dedup();
+    addresses
+        .into_iter()
+        .map(|address| {
+            // SAFETY: `current` leaves out semaphores that aren't aligned and
+            // within the executable's `.probes` section, which only holds the
+            // `u16` semaphores of this crate and `<sys/sdt.h>`, laid out like
+            // `Semaphore` and mapped for the whole process.
+            unsafe { &*(address as usize as *const Semaphore) }
+        })
+        .collect()
+}
+
+/// Read the registry of probes in another process's executable.
+///
+/// Like [`current()`], the addresses are adjusted to their runtime values in
+/// that process. This needs permission to read the process's `/proc` files,
+/// which usually means the same user, or `CAP_SYS_PTRACE`. If the process
+/// keeps latency histograms, `hist::scrape` reads them by the same ID.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn process(pid: u32) -> Result<Registry, Error> {
+    load(&Path::new("/proc").join(std::format!("{}", pid)))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn load_current() -> Result<Registry, Error> {
+    load(Path::new("/proc/self"))
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn load(proc_dir: &Path) -> Result<Registry, Error> {
+    let exe = fs::read_link(proc_dir.join("exe"))?;
+    // Read through the link, in case the file was replaced or deleted.
+    let data = fs::read(proc_dir.join("exe"))?;
+    let elf = Elf::new(&data)?;
+    let maps = fs::read_to_string(proc_dir.join("maps"))?;
+    let bias = load_bias(&elf.loads()?, &exe, &maps)?;
+    let semaphores = elf.probes_section()?;
+    let mut probes = parse(&data)?;
+    for probe in &mut probes {
+        probe.location = probe.location.wrapping_add(bias);
+        probe.semaphore = probe
+            .semaphore
+            .filter(|&s| in_section(&semaphores, s))
+            .map(|s| s.wrapping_add(bias));
+    }
+    Ok(Registry::new(probes))
+}
+
+/// Find the difference between link-time and runtime addresses, by matching
+/// a mapping of the executable against its loadable segments.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn load_bias(segments: &[(u64, u64, u64)], exe: &Path, maps: &str) -> Result<u64, Error> {
+    for line in maps.lines() {
+        // The path is the rest of the line after five fields, and may itself
+        // contain spaces, or end in " (deleted)" just like the `exe` link.
+        let mut fields = line.splitn(6, ' ');
+        let (range, offset, path) = match (
+            fields.next(),
+            fields.nth(1),
+            fields.nth(2).map(str::trim_start),
+        ) {
+            (Some(range), Some(offset), Some(path)) => (range, offset, path),
+            _ => continue,
+        };
+        if Path::new(path) != exe {
+            continue;
+        }
+        let start = range.split('-').next().unwrap_or("");
+        let (start, offset) = match (
+            u64::from_str_radix(start, 16),
+            u64::from_str_radix(offset, 16),
+        ) {
+            (Ok(start), Ok(offset)) => (start, offset),
+            _ => continue,
+        };
+        for &(p_offset, p_vaddr, p_filesz) in segments {
+            let end = add(p_offset, p_filesz)?;
+            if p_offset <= offset && offset < end {
+                let vaddr = add(p_vaddr, offset - p_offset)?;
+                return Ok(start.wrapping_sub(vaddr));
+            }
+        }
+    }
+    Err(Error::Malformed(
+        "executable is not mapped by a loadable segment",
+    ))
+}
+
+struct Section<'a> {
+    name: &'a [u8],
+    kind: u32,
+    addr: u64,
+    offset: u64,
+    size: u64,
+}
+
+struct Elf<'a> {
+    data: &'a [u8],
+    is64: bool,
+    big_endian: bool,
+}
+
+impl<'a> Elf<'a> {
+    fn new(data: &'a [u8]) -> Result<Self, Error> {
+        if data.len() < 16 || &data[..4] != b"\x7fELF" {
+            return Err(Error::Malformed("missing ELF header"));
+        }
+        let is64 = match data[4] {
+            1 => false,
+            2 => true,
+            _ => return Err(Error::Malformed("unknown ELF class")),
+        };
+        let big_endian = match data[5] {
+            1 => false,
+            2 => true,
+            _ => return Err(Error::Malformed("unknown ELF data encoding")),
+        };
+        Ok(Elf {
+            data,
+            is64,
+            big_endian,
+        })
+    }
+
+    fn bytes(&self, offset: u64, len: u64) -> Result<&'a [u8], Error> {
+        let start = usize::try_from(offset).ok();
+        let end = offset
+            .checked_add(len)
+            .and_then(|end| usize::try_from(end).ok());
+        match (start, end) {
+            (Some(start), Some(end)) if end <= self.data.len() => Ok(&self.data[start..end]),
+            _ => Err(Error::Malformed("truncated file")),
+        }
+    }
+
+    fn u16(&self, offset: u64) -> Result<u16, Error> {
+        let b = self.bytes(offset, 2)?;
+        let b = [b[0], b[1]];
+        Ok(if self.big_endian {
+            u16::from_be_bytes(b)
+        } else {
+            u16::from_le_bytes(b)
+        })
+    }
+
+    fn u32(&self, offset: u64) -> Result<u32, Error> {
+        let b = self.bytes(offset, 4)?;
+        let b = [b[0], b[1], b[2], b[3]];
+        Ok(if self.big_endian {
+            u32::from_be_bytes(b)
+        } else {
+            u32::from_le_bytes(b)
+        })
+    }
+
+    fn u64(&self, offset: u64) -> Result<u64, Error> {
+        let b = self.bytes(offset, 8)?;
+        let b = [b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]];
+        Ok(if self.big_endian {
+            u64::from_be_bytes(b)
+        } else {
+            u64::from_le_bytes(b)
+        })
+    }
+
+    /// Read an address or offset, which is sized by the ELF class.
+    fn word(&self, offset: u64) -> Result<u64, Error> {
+        if self.is64 {
+            self.u64(offset)
+        } else {
+            self.u32(offset).map(u64::from)
+        }
+    }
+
+    /// Returns every section header, with its name.
+    fn sections(&self) -> Result<Vec<Section<'a>>, Error> {
+        let (shoff, shentsize, shnum, shstrndx) = if self.is64 {
+            let fields = (self.u16(0x3A)?, self.u16(0x3C)?, self.u16(0x3E)?);
+            (self.u64(0x28)?, fields.0, fields.1, fields.2)
+        } else {
+            let fields = (self.u16(0x2E)?, self.u16(0x30)?, self.u16(0x32)?);
+            (self.u32(0x20)?.into(), fields.0, fields.1, fields.2)
+        };
+        let word = if self.is64 { 8 } else { 4 };
+        let headers = (0..u64::from(shnum))
+            .map(|i| {
+                let sh = add(shoff, mul(i, shentsize.into())?)?;
+                let section = Section {
+                    name: &[],
+                    kind: self.u32(add(sh, 4)?)?,
+                    addr: self.word(add(sh, 8 + word)?)?,
+                    offset: self.word(add(sh, 8 + 2 * word)?)?,
+                    size: self.word(add(sh, 8 + 3 * word)?)?,
+                };
+                Ok((self.u32(sh)?, section))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+        if headers.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Resolve the names once, rather than for every lookup.
+        let (_, strtab) = headers
+            .get(usize::from(shstrndx))
+            .ok_or(Error::Malformed("bad section name table index"))?;
+        let names = self.bytes(strtab.offset, strtab.size)?;
+        headers
+            .iter()
+            .map(|&(name, ref section)| {
+                let name = names
+                    .get(name as usize..)
+                    .ok_or(Error::Malformed("bad section name"))?;
+                Ok(Section {
+                    name: cstr(name)?.0,
+                    ..*section
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the offset, address, and file size of each `PT_LOAD` segment.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn loads(&self) -> Result<Vec<(u64, u64, u64)>, Error> {
+        const PT_LOAD: u32 = 1;
+        let (phoff, phentsize, phnum) = if self.is64 {
+            (self.u64(0x20)?, self.u16(0x36)?, self.u16(0x38)?)
+        } else {
+            (self.u32(0x1C)?.into(), self.u16(0x2A)?, self.u16(0x2C)?)
+        };
+        let mut loads = Vec::new();
+        for i in 0..u64::from(phnum) {
+            let ph = add(phoff, mul(i, phentsize.into())?)?;
+            if self.u32(ph)? != PT_LOAD {
+                continue;
+            }
+            loads.push(if self.is64 {
+                let field = |n: u64| self.u64(add(ph, 8 * n)?);
+                (field(1)?, field(2)?, field(4)?)
+            } else {
+                let field = |n: u64| self.u32(add(ph, 4 * n)?).map(u64::from);
+                (field(1)?, field(2)?, field(4)?)
+            });
+        }
+        Ok(loads)
+    }
+
+    /// Returns the link-time address range of the `.probes` section.
+    fn probes_section(&self) -> Result<Option<Range<u64>>, Error> {
+        let mut range = None;
+        for section in self.sections()? {
+            if section.name == b".probes" {
+                range = Some(section.addr..add(section.addr, section.size)?);
+            }
+        }
+        Ok(range)
+    }
+
+    /// Returns the type, name, and descriptor of each note in a section.
+    fn notes(&self, section: &Section<'_>) -> Result<Vec<Note<'a>>, Error> {
+        let end = add(section.offset, section.size)?;
+        let mut offset = section.offset;
+        let mut notes = Vec::new();
+        while add(offset, 12)? <= end {
+            let namesz = u64::from(self.u32(offset)?);
+            let descsz = u64::from(self.u32(add(offset, 4)?)?);
+            let kind = self.u32(add(offset, 8)?)?;
+            let name = add(offset, 12)?;
+            let desc = add(name, align4(namesz))?;
+            offset = add(desc, align4(descsz))?;
+            if offset > end {
+                return Err(Error::Malformed("truncated note"));
+            }
+            notes.push((kind, self.bytes(name, namesz)?, self.bytes(desc, descsz)?));
+        }
+        Ok(notes)
+    }
+
+    fn parse_notes(&self, section: &Section<'_>, probes: &mut Vec<Probe>) -> Result<(), Error> {
+        for (kind, name, desc) in self.notes(section)? {
+            if kind == NT_STAPSDT && name == b"stapsdt\0" {
+                probes.push(self.parse_probe(desc)?);
+            }
+        }
+        Ok(())
+    }
+
+    fn parse_version(&self, desc: &'a [u8]) -> Result<Version, Error> {
+        if desc.len() < 8 {
+            return Err(Error::Malformed("truncated version descriptor"));
+        }
+        let elf = Elf {
+            data: desc,
+            ..*self
+        };
+        let (version, _) = cstr(&desc[8..])?;
+        Ok(Version {
+            version: String::from_utf8_lossy(version).into_owned(),
+            format: elf.u32(0)?,
+            flags: elf.u32(4)?,
+        })
+    }
+
+    fn parse_abi(&self, desc: &'a [u8]) -> Result<Abi, Error> {
+        if desc.len() < 12 {
+            return Err(Error::Malformed("truncated ABI descriptor"));
+        }
+        let elf = Elf {
+            data: desc,
+            ..*self
+        };
+        Ok(Abi {
+            pointer_width: elf.u32(0)?,
+            big_endian: elf.u32(4)? == 2,
+            arguments: elf.u32(8)?,
+        })
+    }
+
+    fn parse_probe(&self, desc: &[u8]) -> Result<Probe, Error> {
+        let word = if self.is64 { 8 } else { 4 };
+        if desc.len() < 3 * word {
+            return Err(Error::Malformed("truncated probe descriptor"));
+        }
+        let elf = Elf {
+            data: desc,
+            ..*self
+        };
+        let location = elf.word(0)?;
+        let base = elf.word(word as u64)?;
+        let semaphore = elf.word(2 * word as u64)?;
+
+        let (provider, rest) = cstr(&desc[3 * word..])?;
+        let (name, rest) = cstr(rest)?;
+        let (arguments, _) = cstr(rest)?;
+        let string = |s: &[u8]| String::from_utf8_lossy(s).into_owned();
+        Ok(Probe {
+            provider: string(provider),
+            name: string(name),
+            arguments: string(arguments),
+            location,
+            base,
+            semaphore: if semaphore != 0 {
+                Some(semaphore)
+            } else {
+                None
+            },
+        })
+    }
+}
+
+/// Whether a whole, aligned semaphore at `address` is within `section`.
+fn in_section(section: &Option<Range<u64>>, address: u64) -> bool {
+    section.as_ref().map_or(false, |section| {
+        address % 2 == 0
+            && address >= section.start
+            && address
+                .checked_add(2)
+                .map_or(false, |end| end <= section.end)
+    })
+}
+
+/// A note's type, name, and descriptor.
+type Note<'a> = (u32, &'a [u8], &'a [u8]);
+
+/// Round up a note field size, which is at most `u32::MAX`.
+fn align4(n: u64) -> u64 {
+    (n + 3) & !3
+}
+
+/// Add offsets or sizes read from a file, which may be corrupt.
+fn add(a: u64, b: u64) -> Result<u64, Error> {
+    a.checked_add(b)
+        .ok_or(Error::Malformed("offset out of range"))
+}
+
+/// Multiply offsets or sizes read from a file, which may be corrupt.
+fn mul(a: u64, b: u64) -> Result<u64, Error> {
+    a.checked_mul(b)
+        .ok_or(Error::Malformed("offset out of range"))
+}
+
+/// Split a NUL-terminated string from the front of `data`.
+fn cstr(data: &[u8]) -> Result<(&[u8], &[u8]), Error> {
+    match data.iter().position(|&b| b == 0) {
+        Some(nul) => Ok((&data[..nul], &data[nul + 1..])),
+        None => Err(Error::Malformed("unterminated string")),
+    }
+}
+
+#[cfg(all(test, any(target_os = "linux", target_os = "android")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_bias_path() {
+        // Two segments, with the second mapped one page past the first.
+        let segments = [(0, 0, 0x1000), (0x1000, 0x2000, 0x500)];
+        let maps = "\
+5600000000-5600001000 r--p 00000000 08:01 42                         /opt/my app/bin (deleted)
+5600002000-5600003000 r-xp 00001000 08:01 42                         /opt/my app/bin (deleted)
+7f0000000000-7f0000001000 rw-p 00000000 00:00 0
+7ffd00000000-7ffd00021000 rw-p 00000000 00:00 0                      [stack]
+";
+        let exe = Path::new("/opt/my app/bin (deleted)");
+        assert_eq!(load_bias(&segments, exe, maps).unwrap(), 0x5600000000);
+        let maps = maps.replace("/opt/my app/bin (deleted)", "/opt/my");
+        assert!(load_bias(&segments, exe, &maps).is_err());
+    }
+}
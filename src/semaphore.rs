@@ -0,0 +1,62 @@
+//! Probe semaphores
+
+use core::sync::atomic::{AtomicU16, Ordering};
+
+/// A counter of the tools attached to a probe.
+///
+/// Semaphores are not simple flags: each tracer or debugger that attaches to a
+/// probe increments its semaphore, and decrements it again on detach. Several
+/// tools may be attached at once, so the probe stays enabled until the last of
+/// them has left. External tools write the counter directly in the process's
+/// memory, while in-process consumers must use [`attach`](Self::attach) and
+/// [`detach`](Self::detach), which update it atomically.
+///
+/// The layout is a plain `u16`, as expected by SystemTap, GDB, and others, and
+/// as seen by foreign code through an exported semaphore.
+#[repr(transparent)]
+pub struct Semaphore(AtomicU16);
+
+impl Semaphore {
+    /// Create a semaphore with no tools attached.
+    #[inline]
+    pub const fn new() -> Self {
+        Semaphore(AtomicU16::new(0))
+    }
+
+    /// Returns the number of tools currently attached.
+    #[inline]
+    pub fn count(&self) -> u16 {
+        // External writers don't participate in Rust's memory model, but an
+        // atomic load is still re-read every time, compiles to the same plain
+        // load as a volatile read, and doesn't race with `attach` in the eyes
+        // of the thread sanitizer.
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Returns `true` if any tool is attached.
+    #[inline]
+    pub fn enabled(&self) -> bool {
+        self.count() != 0
+    }
+
+    /// Register an in-process consumer, returning the previous count.
+    #[inline]
+    pub fn attach(&self) -> u16 {
+        self.0.fetch_add(1, Ordering::Relaxed)
+    }
+
+    /// Unregister an in-process consumer, returning the previous count.
+    ///
+    /// This must be paired with a previous [`attach`](Self::attach).
+    #[inline]
+    pub fn detach(&self) -> u16 {
+        self.0.fetch_sub(1, Ordering::Relaxed)
+    }
+}
+
+impl Default for Semaphore {
+    #[inline]
+    fn default() -> Self {
+        Semaphore::new()
+    }
+}
@@ -0,0 +1,332 @@
+//! Serving probe statistics to orchestration systems.
+//!
+//! Like SystemTap's `stap-exporter`, this lets a service report which of its
+//! probes are being traced, so orchestration can discover traced services
+//! without a tracer of its own. [`serve`] answers every HTTP request on a
+//! listener with the current [`snapshot`] in the Prometheus text format:
+//!
+//! ```notrust
+//! # TYPE probe_sites gauge
+//! probe_sites{provider="app",name="request"} 2
+//! # TYPE probe_consumers gauge
+//! probe_consumers{provider="app",name="request"} 1
+//! # TYPE probe_hits_total counter
+//! probe_hits_total{provider="app",name="request"} 42
+//! ```
+//!
+//! Consumers are counted from the semaphores of each probe's lazy sites, so
+//! tools that attach without them, like plain `perf`, aren't seen. Hits are
+//! only counted for probes that reach a `Hits` backend, which needs the
+//! `backend` feature, and are zero otherwise.
+//!
+//! The listener can be a `TcpListener`, a `UnixListener` (for `curl
+//! --unix-socket`), or anything else implementing [`Listener`].
+//!
+//! This module requires the `std` feature, and is only available on Linux.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use std::net::TcpListener;
+//!
+//! let listener = TcpListener::bind("127.0.0.1:9100").unwrap();
+//! probe::stats::serve(listener).unwrap();
+//! ```
+
+use crate::registry::{self, Error};
+use core::fmt::Write as _;
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::string::String;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+/// The statistics of one probe.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Stat {
+    /// The probe's provider.
+    pub provider: String,
+    /// The probe's name.
+    pub name: String,
+    /// The number of sites of the probe in the executable.
+    pub sites: usize,
+    /// The most tools attached to any one of the probe's lazy sites.
+    pub consumers: u16,
+    /// The number of firings seen by a `Hits` backend.
+    pub hits: u64,
+}
+
+static HITS: Mutex<BTreeMap<(&'static str, &'static str), u64>> = Mutex::new(BTreeMap::new());
+
+fn hits() -> MutexGuard<'static, BTreeMap<(&'static str, &'static str), u64>> {
+    // The counts are always consistent, even after a panic.
+    HITS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Returns the statistics of every probe in this process's executable.
+///
+/// Probes that only have hits, from outside the executable, are included
+/// with no sites.
+pub fn snapshot() -> Result<Vec<Stat>, &'static Error> {
+    let registry = registry::current()?;
+    let mut hits = hits().clone();
+    let mut stats = Vec::new();
+    for (provider, name, sites) in registry.site_counts() {
+        let consumers = registry::semaphores(provider, name)
+            .iter()
+            .map(|semaphore| semaphore.count())
+            .max()
+            .unwrap_or(0);
+        stats.push(Stat {
+            provider: provider.into(),
+            name: name.into(),
+            sites,
+            consumers,
+            hits: hits.remove(&(provider, name)).unwrap_or(0),
+        });
+    }
+    for ((provider, name), hits) in hits {
+        stats.push(Stat {
+            provider: provider.into(),
+            name: name.into(),
+            sites: 0,
+            consumers: 0,
+            hits,
+        });
+    }
+    Ok(stats)
+}
+
+/// Render a [`snapshot`] in the Prometheus text format.
+pub fn render() -> Result<String, &'static Error> {
+    let stats = snapshot()?;
+    let mut out = String::new();
+    metric(&mut out, &stats, "probe_sites", "gauge", |s| s.sites as u64);
+    metric(&mut out, &stats, "probe_consumers", "gauge", |s| {
+        s.consumers.into()
+    });
+    metric(&mut out, &stats, "probe_hits_total", "counter", |s| s.hits);
+    Ok(out)
+}
+
+fn metric(out: &mut String, stats: &[Stat], metric: &str, kind: &str, value: fn(&Stat) -> u64) {
+    // Writing to a `String` can't fail.
+    let _ = writeln!(out, "# TYPE {} {}", metric, kind);
+    for stat in stats {
+        let _ = writeln!(
+            out,
+            "{}{{provider=\"{}\",name=\"{}\"}} {}",
+            metric,
+            stat.provider,
+            stat.name,
+            value(stat)
+        );
+    }
+}
+
+/// A source of connections for [`serve`].
+pub trait Listener: Send + 'static {
+    /// The type of each connection.
+    type Stream: Read + Write;
+
+    /// Wait for the next connection, or fail with `WouldBlock` if
+    /// non-blocking and there's none.
+    fn accept(&self) -> io::Result<Self::Stream>;
+
+    /// Switch between blocking and non-blocking `accept`.
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()>;
+
+    /// Limit how long each read or write on a connection may block.
+    fn set_timeout(stream: &Self::Stream, timeout: Duration) -> io::Result<()>;
+}
+
+impl Listener for TcpListener {
+    type Stream = TcpStream;
+
+    fn accept(&self) -> io::Result<TcpStream> {
+        TcpListener::accept(self).map(|(stream, _)| stream)
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        TcpListener::set_nonblocking(self, nonblocking)
+    }
+
+    fn set_timeout(stream: &TcpStream, timeout: Duration) -> io::Result<()> {
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))
+    }
+}
+
+impl Listener for UnixListener {
+    type Stream = UnixStream;
+
+    fn accept(&self) -> io::Result<UnixStream> {
+        UnixListener::accept(self).map(|(stream, _)| stream)
+    }
+
+    fn set_nonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        UnixListener::set_nonblocking(self, nonblocking)
+    }
+
+    fn set_timeout(stream: &UnixStream, timeout: Duration) -> io::Result<()> {
+        stream.set_read_timeout(Some(timeout))?;
+        stream.set_write_timeout(Some(timeout))
+    }
+}
+
+/// How often servers check for connections, and for [`crate::shutdown`].
+const POLL: Duration = Duration::from_millis(50);
+
+/// The longest a server spends on one connection, so a client that connects
+/// and sends nothing can't hold up the others.
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+static SERVERS: Mutex<Vec<thread::JoinHandle<()>>> = Mutex::new(Vec::new());
+// Bumped by `shutdown`, which stops every server of an older generation.
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+fn servers() -> MutexGuard<'static, Vec<thread::JoinHandle<()>>> {
+    // The list of threads is always consistent, even after a panic.
+    SERVERS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Answer HTTP requests on `listener` with [`render`], from a background
+/// thread.
+///
+/// Every request gets the same response, whatever its method or path.
+/// Connections are answered one at a time, each within about a second. The
+/// listener is polled without blocking, so [`crate::shutdown`] can stop the
+/// thread and close it.
+pub fn serve<L: Listener>(listener: L) -> io::Result<()> {
+    listener.set_nonblocking(true)?;
+    let mut servers = servers();
+    let generation = GENERATION.load(Ordering::Acquire);
+    let handle = thread::Builder::new()
+        .name("probe-stats".into())
+        .spawn(move || {
+            while GENERATION.load(Ordering::Acquire) == generation {
+                match listener.accept() {
+                    // A client that goes away early is no concern of ours.
+                    // On Linux, the stream doesn't inherit non-blocking mode.
+                    Ok(stream) => {
+                        if L::set_timeout(&stream, TIMEOUT).is_ok() {
+                            drop(respond(stream));
+                        }
+                    }
+                    // Don't spin on a persistent error, like too many open
+                    // files, or while there's no connection.
+                    Err(_) => thread::sleep(POLL),
+                }
+            }
+        })?;
+    servers.push(handle);
+    Ok(())
+}
+
+/// Stop every server, waiting for each until `deadline`, and reset the hit
+/// counts.
+pub(crate) fn shutdown(deadline: Instant) {
+    let mut servers = servers();
+    GENERATION.fetch_add(1, Ordering::AcqRel);
+    for handle in servers.drain(..) {
+        crate::join(handle, deadline);
+    }
+    hits().clear();
+}
+
+fn respond(mut stream: impl Read + Write) -> io::Result<()> {
+    // Read up to the end of the request head, which is all we need, unless
+    // the client is too slow to send it.
+    let deadline = Instant::now() + TIMEOUT;
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.ends_with(b"\r\n\r\n") && head.len() < 8192 && Instant::now() < deadline {
+        let n = match stream.read(&mut buf) {
+            Ok(n) => n,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            // Answer anyway, since the head doesn't matter.
+            Err(err) if is_timeout(&err) => break,
+            Err(err) => return Err(err),
+        };
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+
+    let (status, body) = match render() {
+        Ok(body) => ("200 OK", body),
+        Err(err) => ("500 Internal Server Error", std::format!("{}\n", err)),
+    };
+    write!(
+        stream,
+        "HTTP/1.0 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn is_timeout(err: &io::Error) -> bool {
+    matches!(
+        err.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/// A backend that counts the firings of each probe for [`snapshot`], and
+/// passes them on to `B`.
+///
+/// This requires the `backend` feature.
+///
+/// # Example
+///
+/// ```
+/// use probe::backend::{self, ProbeBackend};
+/// use probe::stats::Hits;
+///
+/// struct Discard;
+///
+/// impl ProbeBackend for Discard {
+///     fn fire(&self, _provider: &'static str, _name: &'static str, _args: &[isize]) {}
+/// }
+///
+/// static HITS: Hits<Discard> = Hits::new(Discard);
+/// backend::set_backend(&HITS).unwrap();
+/// ```
+#[cfg(feature = "backend")]
+#[derive(Debug)]
+pub struct Hits<B> {
+    inner: B,
+}
+
+#[cfg(feature = "backend")]
+impl<B> Hits<B> {
+    /// Wrap `inner`, counting every firing that reaches it.
+    pub const fn new(inner: B) -> Hits<B> {
+        Hits { inner }
+    }
+
+    /// Returns the wrapped backend.
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+}
+
+#[cfg(feature = "backend")]
+impl<B: crate::backend::ProbeBackend> crate::backend::ProbeBackend for Hits<B> {
+    fn enabled(&self, provider: &'static str, name: &'static str) -> bool {
+        self.inner.enabled(provider, name)
+    }
+
+    fn fire(&self, provider: &'static str, name: &'static str, args: &[isize]) {
+        *hits().entry((provider, name)).or_insert(0) += 1;
+        self.inner.fire(provider, name, args);
+    }
+}
@@ -0,0 +1,61 @@
+//! Which probe implementation was compiled in, and what it can do.
+//!
+//! Libraries can use this to adapt to the platform, for instance skipping
+//! the preparation of expensive probe context entirely when probes are no-ops.
+//!
+//! # Example
+//!
+//! ```
+//! if probe::is_supported() {
+//!     // Probes may be seen by a tracer, so build their context.
+//! }
+//! ```
+
+/// A platform implementation of probes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Platform {
+    /// SystemTap SDT notes, on Linux and Android.
+    SystemTap,
+    /// Host imports on WASI, with the `wasi-host` feature.
+    WasiHost,
+    /// Probes compile to nothing, besides evaluating their arguments.
+    NoOp,
+}
+
+impl Platform {
+    /// Returns `true` unless probes are no-ops.
+    pub const fn is_supported(self) -> bool {
+        !matches!(self, Platform::NoOp)
+    }
+
+    /// Returns `true` if lazy probes have semaphores that tools can attach,
+    /// and which GDB lists with `info probes`. The WASI host decides about
+    /// lazy probes with its own import instead.
+    pub const fn has_semaphores(self) -> bool {
+        matches!(self, Platform::SystemTap)
+    }
+
+    /// Returns `true` if probe arguments can carry strings, rather than only
+    /// integers. No platform supports this yet.
+    pub const fn has_strings(self) -> bool {
+        false
+    }
+
+    /// Returns `true` if probes can be defined at runtime, rather than only
+    /// at compile time. No platform supports this yet.
+    pub const fn has_dynamic_probes(self) -> bool {
+        false
+    }
+}
+
+/// The platform implementation compiled into this build.
+pub const PLATFORM: Platform = crate::platform::PLATFORM;
+
+/// Returns `true` unless probes are no-ops on this platform.
+///
+/// When the crate is built with a sanitizer, this is `false` even on a
+/// supported platform.
+pub const fn is_supported() -> bool {
+    PLATFORM.is_supported()
+}
@@ -0,0 +1,114 @@
+//! Throttling an in-process backend.
+//!
+//! A probe in a hot loop can fire far more often than a telemetry pipeline
+//! can keep up with. [`Throttle`] wraps another [`ProbeBackend`] and only
+//! passes each probe through once per minimum interval, dropping the firings
+//! in between. Every dropped firing is counted, so the data loss is visible.
+//!
+//! This module requires both the `backend` and `std` features.
+//!
+//! # Example
+//!
+//! ```
+//! use probe::backend::{self, ProbeBackend};
+//! use probe::throttle::Throttle;
+//! use std::time::Duration;
+//!
+//! struct Printer;
+//!
+//! impl ProbeBackend for Printer {
+//!     fn fire(&self, provider: &'static str, name: &'static str, args: &[isize]) {
+//!         println!("{}:{} {:?}", provider, name, args);
+//!     }
+//! }
+//!
+//! let throttle = Throttle::new(Printer, Duration::from_millis(100));
+//! let throttle: &'static Throttle<Printer> = Box::leak(Box::new(throttle));
+//! backend::set_backend(throttle).unwrap();
+//! for i in 0..1000 {
+//!     probe::probe!(foo, hot, i);
+//! }
+//! assert!(throttle.dropped() > 0);
+//! ```
+
+use crate::backend::ProbeBackend;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Default)]
+struct Entry {
+    last: Option<Instant>,
+    dropped: u64,
+}
+
+/// A backend that passes each probe to `B` at most once per interval.
+pub struct Throttle<B> {
+    inner: B,
+    interval: Duration,
+    probes: Mutex<HashMap<(&'static str, &'static str), Entry>>,
+    dropped: AtomicU64,
+}
+
+impl<B: ProbeBackend> Throttle<B> {
+    /// Wrap `inner`, so each probe reaches it at most once per `interval`.
+    pub fn new(inner: B, interval: Duration) -> Throttle<B> {
+        Throttle {
+            inner,
+            interval,
+            probes: Mutex::new(HashMap::new()),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns the wrapped backend.
+    pub fn inner(&self) -> &B {
+        &self.inner
+    }
+
+    /// Returns the total number of firings dropped so far.
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of firings of one probe dropped so far.
+    pub fn dropped_by(&self, provider: &str, name: &str) -> u64 {
+        let probes = self.probes.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = probes
+            .iter()
+            .find(|(key, _)| key.0 == provider && key.1 == name);
+        entry.map_or(0, |(_, entry)| entry.dropped)
+    }
+
+    /// Checks whether the probe may fire now, starting its next interval if
+    /// `claim` is set, or counting a drop if not allowed.
+    fn admit(&self, provider: &'static str, name: &'static str, claim: bool) -> bool {
+        let now = Instant::now();
+        let mut probes = self.probes.lock().unwrap_or_else(|e| e.into_inner());
+        let entry = probes.entry((provider, name)).or_default();
+        let allowed = entry
+            .last
+            .map_or(true, |last| now.duration_since(last) >= self.interval);
+        if !allowed {
+            entry.dropped += 1;
+            self.dropped.fetch_add(1, Ordering::Relaxed);
+        } else if claim {
+            entry.last = Some(now);
+        }
+        allowed
+    }
+}
+
+impl<B: ProbeBackend> ProbeBackend for Throttle<B> {
+    fn enabled(&self, provider: &'static str, name: &'static str) -> bool {
+        // Lazy probes don't evaluate their arguments while throttled.
+        self.inner.enabled(provider, name) && self.admit(provider, name, false)
+    }
+
+    fn fire(&self, provider: &'static str, name: &'static str, args: &[isize]) {
+        if self.admit(provider, name, true) {
+            self.inner.fire(provider, name, args);
+        }
+    }
+}
@@ -0,0 +1,350 @@
+//! Forwarding probes to Linux `user_events`.
+//!
+//! Since Linux 6.4, the kernel's `user_events` interface lets a process
+//! register its own trace events, which then appear in tracefs next to the
+//! kernel's tracepoints, for plain ftrace and `perf` to record without any
+//! uprobes. [`UserEvents`] is a [`ProbeBackend`] that registers each probe as
+//! a `user_events` event the first time it's seen. By default, the event is
+//! named `provider_name`, with fields `arg0` through `arg11` of type `s64`,
+//! and unused fields are zero. An event only appears in tracefs once its
+//! probe has first been reached.
+//!
+//! The kernel sets a bit in this process's memory while a tracer has the
+//! event enabled, and that's all a lazy probe checks before evaluating its
+//! arguments, so disabled probes don't make any system call.
+//!
+//! With [`Format::EventHeader`], events use the self-describing EventHeader
+//! encoding of the LinuxTracepoints project instead, which tools like
+//! `decode-perf` and Perfetto's importer can decode without knowing each
+//! event's fields. Each provider then has a single tracepoint, named
+//! `provider_L5K1` for the verbose level and keyword 1, and every event
+//! carries its probe's name and its arguments as signed 64-bit fields.
+//!
+//! This needs write access to `user_events_data` in tracefs, which is usually
+//! limited to root. This module requires both the `backend` and `std`
+//! features, and is only available on Linux.
+//!
+//! # Example
+//!
+//! ```no_run
+//! use probe::backend;
+//! use probe::user_events::UserEvents;
+//!
+//! let events = UserEvents::new().expect("user_events is unavailable");
+//! backend::set_backend(Box::leak(Box::new(events))).unwrap();
+//! probe::probe!(foo, bar, 1, 2);
+//! ```
+//!
+//! ```notrust
+//! # echo 1 > /sys/kernel/tracing/events/user_events/foo_bar/enable
+//! ```
+
+use crate::backend::ProbeBackend;
+use core::ffi::{c_int, c_ulong};
+use core::fmt::Write as _;
+use core::mem;
+use core::sync::atomic::{AtomicU32, Ordering};
+use std::boxed::Box;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+use std::string::String;
+use std::sync::Mutex;
+use std::vec::Vec;
+
+/// The most arguments a backend sees, the same as `<sys/sdt.h>`.
+const MAX_ARGS: usize = 12;
+
+// From <eventheader.h> in LinuxTracepoints.
+const EVENTHEADER_FIELDS: &str =
+    "u8 eventheader_flags; u8 version; u16 id; u16 tag; u8 opcode; u8 level";
+const FLAG_POINTER64: u8 = 0x01;
+const FLAG_LITTLE_ENDIAN: u8 = 0x02;
+const FLAG_EXTENSION: u8 = 0x04;
+const EXTENSION_METADATA: u16 = 1;
+const ENCODING_VALUE64: u8 = 5;
+const ENCODING_CHAIN: u8 = 0x80;
+const FORMAT_SIGNED_INT: u8 = 2;
+const LEVEL_VERBOSE: u8 = 5;
+const KEYWORD: u64 = 1;
+
+const PATHS: [&str; 2] = [
+    "/sys/kernel/tracing/user_events_data",
+    "/sys/kernel/debug/tracing/user_events_data",
+];
+
+// From <linux/user_events.h>.
+#[repr(C, packed)]
+struct UserReg {
+    size: u32,
+    enable_bit: u8,
+    enable_size: u8,
+    flags: u16,
+    enable_addr: u64,
+    name_args: u64,
+    write_index: u32,
+}
+
+#[repr(C, packed)]
+struct UserUnreg {
+    size: u32,
+    disable_bit: u8,
+    reserved: u8,
+    reserved2: u16,
+    disable_addr: u64,
+}
+
+// The generic `_IOC` encoding, with a pointer-sized argument like the header.
+const fn ioc(dir: c_ulong, nr: c_ulong) -> c_ulong {
+    (dir << 30) | ((mem::size_of::<usize>() as c_ulong) << 16) | ((b'*' as c_ulong) << 8) | nr
+}
+
+const DIAG_IOCSREG: c_ulong = ioc(3, 0);
+const DIAG_IOCSUNREG: c_ulong = ioc(1, 2);
+
+#[cfg(not(any(target_env = "musl", target_os = "android")))]
+type Request = c_ulong;
+#[cfg(any(target_env = "musl", target_os = "android"))]
+type Request = c_int;
+
+extern "C" {
+    fn ioctl(fd: c_int, request: Request, ...) -> c_int;
+}
+
+struct Event {
+    // The kernel sets bit 0 while the event is enabled. It's boxed so its
+    // address stays the same for as long as it's registered.
+    enabled: Box<AtomicU32>,
+    index: u32,
+}
+
+impl Event {
+    fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed) & 1 != 0
+    }
+}
+
+/// How [`UserEvents`] lays out its events.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Format {
+    /// An event per probe, named `provider_name`, with fields `arg0` through
+    /// `arg11` of type `s64`, which ftrace and `perf` can print directly.
+    Fields,
+    /// A tracepoint per provider, whose events are self-describing in the
+    /// EventHeader encoding.
+    EventHeader,
+}
+
+/// A backend that forwards probes to the kernel's `user_events`.
+pub struct UserEvents {
+    file: File,
+    format: Format,
+    // Events that failed to register are kept as `None`, so they're not
+    // retried on every firing.
+    events: Mutex<HashMap<(&'static str, &'static str), Option<Event>>>,
+}
+
+impl UserEvents {
+    /// Open `user_events_data` in tracefs, to register events in the
+    /// [`Format::Fields`] layout.
+    pub fn new() -> io::Result<UserEvents> {
+        UserEvents::with_format(Format::Fields)
+    }
+
+    /// Open `user_events_data` in tracefs, to register events in `format`.
+    pub fn with_format(format: Format) -> io::Result<UserEvents> {
+        let mut result = Err(io::ErrorKind::NotFound.into());
+        for path in PATHS {
+            result = OpenOptions::new().read(true).write(true).open(path);
+            if result.is_ok() {
+                break;
+            }
+        }
+        Ok(UserEvents {
+            file: result?,
+            format,
+            events: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn register(&self, provider: &str, name: &str) -> io::Result<Event> {
+        // Writing to a `String` can't fail.
+        let mut name_args = String::new();
+        match self.format {
+            Format::Fields => {
+                let _ = write!(name_args, "{}_{}", provider, name);
+                for i in 0..MAX_ARGS {
+                    let sep = if i == 0 { " " } else { ";" };
+                    let _ = write!(name_args, "{}s64 arg{}", sep, i);
+                }
+            }
+            // Each probe registers the provider's tracepoint again, which
+            // the kernel shares, but with its own enable bit.
+            Format::EventHeader => {
+                let _ = write!(
+                    name_args,
+                    "{}_L{:x}K{:x} {}",
+                    provider, LEVEL_VERBOSE, KEYWORD, EVENTHEADER_FIELDS
+                );
+            }
+        }
+        name_args.push('\0');
+
+        let enabled = Box::new(AtomicU32::new(0));
+        let mut reg = UserReg {
+            size: mem::size_of::<UserReg>() as u32,
+            enable_bit: 0,
+            enable_size: 4,
+            flags: 0,
+            enable_addr: &*enabled as *const AtomicU32 as u64,
+            name_args: name_args.as_ptr() as u64,
+            write_index: 0,
+        };
+        // SAFETY: `reg` matches the kernel's layout, and the enable word
+        // outlives the registration, which `Drop` removes.
+        let ret = unsafe {
+            ioctl(
+                self.file.as_raw_fd(),
+                DIAG_IOCSREG as Request,
+                &mut reg as *mut UserReg,
+            )
+        };
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(Event {
+            enabled,
+            index: reg.write_index,
+        })
+    }
+
+    fn with_event<R>(
+        &self,
+        provider: &'static str,
+        name: &'static str,
+        f: impl FnOnce(&Event) -> R,
+    ) -> Option<R> {
+        let mut events = self.events.lock().unwrap_or_else(|e| e.into_inner());
+        let event = events
+            .entry((provider, name))
+            .or_insert_with(|| self.register(provider, name).ok());
+        event.as_ref().map(f)
+    }
+}
+
+impl ProbeBackend for UserEvents {
+    fn enabled(&self, provider: &'static str, name: &'static str) -> bool {
+        self.with_event(provider, name, Event::is_enabled)
+            .unwrap_or(false)
+    }
+
+    fn fire(&self, provider: &'static str, name: &'static str, args: &[isize]) {
+        self.with_event(provider, name, |event| {
+            if !event.is_enabled() {
+                return;
+            }
+            // There's nowhere to report a failure to write.
+            match self.format {
+                Format::Fields => {
+                    let mut data = [0; 4 + 8 * MAX_ARGS];
+                    data[..4].copy_from_slice(&event.index.to_ne_bytes());
+                    for (chunk, &arg) in data[4..].chunks_mut(8).zip(args) {
+                        chunk.copy_from_slice(&(arg as i64).to_ne_bytes());
+                    }
+                    let _ = (&self.file).write(&data);
+                }
+                Format::EventHeader => {
+                    let mut data = Vec::from(event.index.to_ne_bytes());
+                    event_header(&mut data, name, args);
+                    let _ = (&self.file).write(&data);
+                }
+            }
+        });
+    }
+}
+
+/// Append an EventHeader event named `name` with `args` as its fields.
+fn event_header(data: &mut Vec<u8>, name: &str, args: &[isize]) {
+    let mut flags = FLAG_EXTENSION;
+    if cfg!(target_pointer_width = "64") {
+        flags |= FLAG_POINTER64;
+    }
+    if cfg!(target_endian = "little") {
+        flags |= FLAG_LITTLE_ENDIAN;
+    }
+    // The flags, version, id, tag, opcode and level, with no id or tag, and
+    // the opcode for a plain informational event.
+    data.extend_from_slice(&[flags, 0, 0, 0, 0, 0, 0, LEVEL_VERBOSE]);
+
+    // A metadata extension with the event's name, then the name, encoding
+    // and format of each field. Its size is filled in once it's known.
+    let start = data.len();
+    data.extend_from_slice(&[0; 2]);
+    data.extend_from_slice(&EXTENSION_METADATA.to_ne_bytes());
+    data.extend_from_slice(name.as_bytes());
+    data.push(0);
+    for i in 0..args.len() {
+        // Writing to a `Vec` can't fail.
+        let _ = write!(data, "arg{}\0", i);
+        data.extend_from_slice(&[ENCODING_VALUE64 | ENCODING_CHAIN, FORMAT_SIGNED_INT]);
+    }
+    let size = (data.len() - start - 4) as u16;
+    data[start..start + 2].copy_from_slice(&size.to_ne_bytes());
+
+    for &arg in args {
+        data.extend_from_slice(&(arg as i64).to_ne_bytes());
+    }
+}
+
+impl Drop for UserEvents {
+    fn drop(&mut self) {
+        let events = self.events.get_mut().unwrap_or_else(|e| e.into_inner());
+        for (_, event) in events.drain() {
+            let event = match event {
+                Some(event) => event,
+                None => continue,
+            };
+            let mut unreg = UserUnreg {
+                size: mem::size_of::<UserUnreg>() as u32,
+                disable_bit: 0,
+                reserved: 0,
+                reserved2: 0,
+                disable_addr: &*event.enabled as *const AtomicU32 as u64,
+            };
+            // SAFETY: after this, the kernel no longer writes the enable word.
+            let ret = unsafe {
+                ioctl(
+                    self.file.as_raw_fd(),
+                    DIAG_IOCSUNREG as Request,
+                    &mut unreg as *mut UserUnreg,
+                )
+            };
+            if ret < 0 {
+                // The kernel may still write it, so it must never be freed.
+                Box::leak(event.enabled);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(all(target_pointer_width = "64", target_endian = "little"))]
+    fn event_header_layout() {
+        let mut data = Vec::new();
+        event_header(&mut data, "bar", &[1, -2]);
+        let mut expected = Vec::from([0x07, 0, 0, 0, 0, 0, 0, 5]);
+        expected.extend_from_slice(&[18, 0, 1, 0]);
+        expected.extend_from_slice(b"bar\0");
+        expected.extend_from_slice(b"arg0\0\x85\x02");
+        expected.extend_from_slice(b"arg1\0\x85\x02");
+        expected.extend_from_slice(&1i64.to_le_bytes());
+        expected.extend_from_slice(&(-2i64).to_le_bytes());
+        assert_eq!(data, expected);
+    }
+}
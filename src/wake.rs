@@ -0,0 +1,100 @@
+//! Measuring wake-to-poll latency of futures.
+//!
+//! When a future is woken, its executor still has to get around to polling
+//! it again, and that scheduling delay is invisible to the future itself.
+//! [`WakeLatency`] wraps a future and substitutes its own waker, recording
+//! when the future was woken, so the next poll can report how long it took.
+//! This works with any executor.
+//!
+//! This module requires the `std` feature.
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::sync::{Arc, Mutex};
+use std::task::Wake;
+use std::time::Instant;
+
+/// A future that reports the latency between each wake and the next poll.
+///
+/// This is usually created by [`probe_wake!`](crate::probe_wake). Each time
+/// the inner future is polled after being woken, `fire` is called with the
+/// instant of the earliest wake since the last poll.
+pub struct WakeLatency<Fut, F> {
+    future: Fut,
+    waker: Option<(Arc<WakeTime>, Waker)>,
+    fire: F,
+}
+
+struct WakeTime {
+    woken: Mutex<Option<Instant>>,
+    waker: Waker,
+}
+
+impl Wake for WakeTime {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        if let Ok(mut woken) = self.woken.lock() {
+            woken.get_or_insert_with(Instant::now);
+        }
+        self.waker.wake_by_ref();
+    }
+}
+
+impl<Fut, F> WakeLatency<Fut, F>
+where
+    Fut: Future,
+    F: FnMut(Instant),
+{
+    /// Wrap `future`, calling `fire` with the wake time when it's next polled.
+    #[inline]
+    pub fn new(future: Fut, fire: F) -> Self {
+        WakeLatency {
+            future,
+            waker: None,
+            fire,
+        }
+    }
+}
+
+impl<Fut, F> Future for WakeLatency<Fut, F>
+where
+    Fut: Future,
+    F: FnMut(Instant),
+{
+    type Output = Fut::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // SAFETY: `future` is structurally pinned, and never moved out of
+        // `self`. The other fields are never pinned.
+        let this = unsafe { self.get_unchecked_mut() };
+        let future = unsafe { Pin::new_unchecked(&mut this.future) };
+
+        if let Some((state, _)) = &this.waker {
+            let woken = state.woken.lock().ok().and_then(|mut woken| woken.take());
+            if let Some(woken) = woken {
+                (this.fire)(woken);
+            }
+        }
+
+        // Wrap the executor's waker, unless we already have for this one.
+        let current = this.waker.as_ref().map(|(state, _)| &state.waker);
+        if !current.map_or(false, |waker| waker.will_wake(cx.waker())) {
+            let state = Arc::new(WakeTime {
+                woken: Mutex::new(None),
+                waker: cx.waker().clone(),
+            });
+            let waker = Waker::from(state.clone());
+            this.waker = Some((state, waker));
+        }
+
+        let waker = match &this.waker {
+            Some((_, waker)) => waker,
+            None => unreachable!(),
+        };
+        future.poll(&mut Context::from_waker(waker))
+    }
+}
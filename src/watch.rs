@@ -0,0 +1,289 @@
+//! Running code when a tracer attaches to or detaches from a probe.
+//!
+//! A tool that attaches in the middle of a run misses whatever happened
+//! before, like the configuration the process started with, or the current
+//! value of counters that probes only report as deltas. This module watches
+//! the semaphores of lazy probes from a background thread, and calls a hook
+//! when a probe goes from having no tools attached to having one, so it can
+//! fire a burst of probes describing the current state. Likewise, subsystems
+//! that aggregate data for probes, like histograms or batches, can flush or
+//! reset when the last tool detaches, so the next session starts fresh.
+//! [`on_change`] sees both, to start and stop auxiliary collection exactly
+//! while tools are attached.
+//!
+//! Hot code that checks many lazy probes can instead [`track`] them, giving
+//! each a bit in one shared word, so [`attached`] checks them all with a
+//! single load rather than touching each probe's semaphore.
+//!
+//! The semaphores are polled every [`INTERVAL`], so a hook may run a little
+//! after the tool attaches, and won't see attachments shorter than that.
+//! [`shutdown`](crate::shutdown) stops the thread and drops every hook.
+//!
+//! This module requires the `std` feature, and is only available on Linux.
+//!
+//! # Example
+//!
+//! ```
+//! use probe::{probe, probe_lazy, watch};
+//!
+//! fn serve() {
+//!     probe_lazy!(app, request, 42);
+//! }
+//!
+//! # if probe::is_supported() {
+//! watch::on_attach("app", "request", || {
+//!     probe!(app, config, 8080, 16);
+//! })
+//! .unwrap();
+//! # }
+//! serve();
+//! ```
+
+use crate::registry;
+use crate::Semaphore;
+use std::boxed::Box;
+use std::error;
+use std::fmt;
+use std::format;
+use std::mem;
+use std::panic::{self, AssertUnwindSafe};
+use std::string::String;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use std::vec::Vec;
+
+/// How often the watcher thread checks semaphores.
+pub const INTERVAL: Duration = Duration::from_millis(50);
+
+struct Hook {
+    semaphores: Vec<&'static Semaphore>,
+    attached: bool,
+    // The transition this runs on, or `None` for both.
+    on: Option<bool>,
+    f: Box<dyn FnMut(bool) + Send>,
+}
+
+impl Hook {
+    fn attached(&self) -> bool {
+        self.semaphores.iter().any(|semaphore| semaphore.enabled())
+    }
+}
+
+/// The error returned when a probe can't be watched.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum WatchError {
+    /// No lazy site of the named probe has a semaphore to watch.
+    NoSemaphore(String),
+    /// Every bit of the [`attached`] word is already taken.
+    BitmapFull,
+}
+
+impl fmt::Display for WatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchError::NoSemaphore(probe) => write!(f, "no semaphore found for {}", probe),
+            WatchError::BitmapFull => f.write_str("no bits left to track another probe"),
+        }
+    }
+}
+
+impl error::Error for WatchError {}
+
+static HOOKS: Mutex<Vec<Hook>> = Mutex::new(Vec::new());
+static WATCHER: Mutex<Option<JoinHandle<()>>> = Mutex::new(None);
+// The probes given a bit by `track`, in bit order.
+static TRACKED: Mutex<Vec<(String, String)>> = Mutex::new(Vec::new());
+static ATTACHED: AtomicUsize = AtomicUsize::new(0);
+// Bumped by `shutdown`, which stops any watcher of an older generation.
+static GENERATION: AtomicUsize = AtomicUsize::new(0);
+
+/// Call `f` each time a tool attaches to the named probe in this process.
+///
+/// The hook runs on the watcher thread whenever the probe goes from no
+/// tools attached, across all of its lazy sites, to at least one. That
+/// includes the first check, if a tool is already attached. A hook that
+/// panics stays registered, and doesn't stop the others. This fails if the
+/// probe has no lazy site with a semaphore to watch.
+pub fn on_attach<F>(provider: &str, name: &str, mut f: F) -> Result<(), WatchError>
+where
+    F: FnMut() + Send + 'static,
+{
+    add(provider, name, Some(true), Box::new(move |_| f()))
+}
+
+/// Call `f` each time the last tool detaches from the named probe in this
+/// process.
+///
+/// This is the reverse of [`on_attach`]: the hook runs whenever the probe
+/// goes from at least one tool attached, across all of its lazy sites, to
+/// none. This fails if the probe has no lazy site with a semaphore to watch.
+pub fn on_detach<F>(provider: &str, name: &str, mut f: F) -> Result<(), WatchError>
+where
+    F: FnMut() + Send + 'static,
+{
+    add(provider, name, Some(false), Box::new(move |_| f()))
+}
+
+/// Call `f` with `true` each time a tool attaches to the named probe in this
+/// process, and with `false` each time the last one detaches.
+///
+/// This combines [`on_attach`] and [`on_detach`] in one hook, which sees the
+/// transitions in order. This fails if the probe has no lazy site with a
+/// semaphore to watch.
+pub fn on_change<F>(provider: &str, name: &str, f: F) -> Result<(), WatchError>
+where
+    F: FnMut(bool) + Send + 'static,
+{
+    add(provider, name, None, Box::new(f))
+}
+
+/// Watch the named probe, and return the bit that [`attached`] sets while a
+/// tool is attached to it.
+///
+/// Tracking the same probe again returns the same bit. Like the other hooks,
+/// the bit is updated within an [`INTERVAL`] of the change. This fails if the
+/// probe has no lazy site with a semaphore to watch, or if every bit of the
+/// word is already taken.
+///
+/// # Example
+///
+/// ```
+/// use probe::{probe_lazy, watch};
+///
+/// fn hot() {
+///     probe_lazy!(app, parse, 1);
+///     probe_lazy!(app, eval, 2);
+/// }
+///
+/// # if probe::is_supported() {
+/// let parse = watch::track("app", "parse").unwrap();
+/// let eval = watch::track("app", "eval").unwrap();
+/// if watch::attached() & (parse | eval) != 0 {
+///     hot();
+/// }
+/// # }
+/// ```
+pub fn track(provider: &str, name: &str) -> Result<usize, WatchError> {
+    let mut tracked = tracked();
+    let i = tracked.iter().position(|(p, n)| p == provider && n == name);
+    if let Some(i) = i {
+        return Ok(1 << i);
+    }
+    if tracked.len() == usize::BITS as usize {
+        return Err(WatchError::BitmapFull);
+    }
+    let bit = 1 << tracked.len();
+    on_change(provider, name, move |attached| {
+        if attached {
+            ATTACHED.fetch_or(bit, Ordering::Relaxed);
+        } else {
+            ATTACHED.fetch_and(!bit, Ordering::Relaxed);
+        }
+    })?;
+    tracked.push((provider.into(), name.into()));
+    Ok(bit)
+}
+
+/// Returns the bits of every [`track`]ed probe that has a tool attached.
+#[inline]
+pub fn attached() -> usize {
+    ATTACHED.load(Ordering::Relaxed)
+}
+
+fn add(
+    provider: &str,
+    name: &str,
+    on: Option<bool>,
+    f: Box<dyn FnMut(bool) + Send>,
+) -> Result<(), WatchError> {
+    let semaphores = registry::semaphores(provider, name);
+    if semaphores.is_empty() {
+        return Err(WatchError::NoSemaphore(format!("{}:{}", provider, name)));
+    }
+
+    // Holding the watcher for the rest keeps `shutdown` from running
+    // between adding the hook and starting the thread.
+    let mut thread = watcher();
+    lock().push(Hook {
+        semaphores,
+        attached: false,
+        on,
+        f,
+    });
+    if thread.as_ref().map_or(true, JoinHandle::is_finished) {
+        let generation = GENERATION.load(Ordering::Acquire);
+        let handle = thread::Builder::new()
+            .name("probe-watch".into())
+            .spawn(move || watch(generation))
+            .expect("failed to spawn the probe watcher thread");
+        *thread = Some(handle);
+    }
+    Ok(())
+}
+
+/// Stop the watcher thread, and drop every hook and tracked bit, waiting for
+/// the thread until `deadline`.
+pub(crate) fn shutdown(deadline: Instant) {
+    let mut tracked = tracked();
+    let mut thread = watcher();
+    {
+        let mut hooks = lock();
+        GENERATION.fetch_add(1, Ordering::AcqRel);
+        hooks.clear();
+    }
+    if let Some(handle) = thread.take() {
+        crate::join(handle, deadline);
+    }
+    tracked.clear();
+    ATTACHED.store(0, Ordering::Relaxed);
+}
+
+fn lock() -> MutexGuard<'static, Vec<Hook>> {
+    // Hooks never run under the lock, and the list is always consistent.
+    HOOKS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn watcher() -> MutexGuard<'static, Option<JoinHandle<()>>> {
+    WATCHER.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn tracked() -> MutexGuard<'static, Vec<(String, String)>> {
+    TRACKED.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn watch(generation: usize) {
+    loop {
+        // Run the hooks without holding the lock, so they can add more.
+        let mut hooks = {
+            let mut hooks = lock();
+            if GENERATION.load(Ordering::Acquire) != generation {
+                return;
+            }
+            mem::take(&mut *hooks)
+        };
+        for hook in &mut hooks {
+            let attached = hook.attached();
+            if attached != hook.attached && hook.on.map_or(true, |on| on == attached) {
+                // The panic is already reported, and the hook can try again
+                // next time.
+                let f = &mut hook.f;
+                let _ = panic::catch_unwind(AssertUnwindSafe(|| f(attached)));
+            }
+            hook.attached = attached;
+        }
+        {
+            let mut current = lock();
+            // `shutdown` has dropped the hooks, which leaves these to us.
+            if GENERATION.load(Ordering::Acquire) != generation {
+                return;
+            }
+            // Keep any that were added in the meantime.
+            hooks.append(&mut current);
+            *current = hooks;
+        }
+        thread::sleep(INTERVAL);
+    }
+}
@@ -0,0 +1,89 @@
+// Probes fired from inside the global allocator and thread-local destructors,
+// which must not allocate or deadlock.
+
+use probe::{probe, probe_lazy, probe_scope};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+struct Probed;
+
+static ALLOCS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Probed {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCS.fetch_add(1, Ordering::Relaxed);
+        let _scope = probe_scope!(alloc, enter, exit, layout.size());
+        let ptr = System.alloc(layout);
+        probe!(alloc, alloc, ptr, layout.size(), layout.align());
+        let _ = probe_lazy!(alloc, lazy, ptr);
+        #[cfg(feature = "std")]
+        probe!(
+            #[baggage]
+            alloc,
+            baggage,
+            ptr
+        );
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        probe!(
+            #[ordered]
+            alloc,
+            dealloc,
+            ptr,
+            layout.size()
+        );
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: Probed = Probed;
+
+#[test]
+fn from_allocator() {
+    let before = ALLOCS.load(Ordering::Relaxed);
+    let v: Vec<u64> = (0..1000).collect();
+    let s = format!("{:?}", &v[..3]);
+    assert_eq!(s, "[0, 1, 2]");
+    assert!(ALLOCS.load(Ordering::Relaxed) > before);
+}
+
+struct Exit(Cell<u64>);
+
+impl Drop for Exit {
+    fn drop(&mut self) {
+        probe!(tls, exit, self.0.get());
+        let _ = probe_lazy!(tls, lazy_exit, self.0.get());
+        #[cfg(feature = "std")]
+        {
+            // The baggage may already be gone, and then it reads zero.
+            probe!(
+                #[baggage]
+                tls,
+                baggage_exit
+            );
+            probe::baggage::set(1);
+        }
+        // Allocating here goes through the probed allocator too.
+        drop(vec![self.0.get(); 16]);
+    }
+}
+
+thread_local! {
+    static EXIT: Exit = const { Exit(Cell::new(0)) };
+}
+
+#[test]
+fn from_tls_destructor() {
+    thread::spawn(|| {
+        #[cfg(feature = "std")]
+        probe::baggage::set(7);
+        EXIT.with(|exit| exit.0.set(42));
+    })
+    .join()
+    .unwrap();
+}
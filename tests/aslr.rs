@@ -0,0 +1,170 @@
+//! Resolve probe addresses in a copy of this test binary, launched with and
+//! without address randomization. The relocation CI job also builds this
+//! without PIE, so all of those layouts are covered.
+
+#![cfg(all(
+    feature = "std",
+    any(target_os = "linux", target_os = "android"),
+    not(probe_noop)
+))]
+
+use probe::{consumer, probe_lazy, registry};
+use std::env;
+use std::io::{BufRead, BufReader, Lines};
+use std::process::{ChildStdout, Command, Stdio};
+use std::ptr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Set in the environment of the child process.
+const CHILD: &str = "PROBE_ASLR_CHILD";
+
+#[inline(never)]
+fn site() -> bool {
+    probe_lazy!(
+        #[export_name = "probe_aslr_semaphore"]
+        aslr,
+        site,
+        1
+    )
+}
+
+extern "C" {
+    #[link_name = "probe_aslr_semaphore"]
+    static SEMAPHORE: u16;
+}
+
+/// The runtime addresses of `site` and its semaphore.
+#[derive(Debug, PartialEq, Eq)]
+struct Layout {
+    site: u64,
+    semaphore: u64,
+}
+
+impl Layout {
+    fn current() -> Layout {
+        Layout {
+            site: site as *const () as u64,
+            semaphore: ptr::addr_of!(SEMAPHORE) as u64,
+        }
+    }
+
+    /// Check a registry's idea of the probe against the actual layout.
+    fn check(&self, probes: &[registry::Probe]) {
+        // The backend feature adds an eager site too, without a semaphore.
+        let lazy: Vec<_> = probes.iter().filter(|p| p.semaphore.is_some()).collect();
+        assert_eq!(lazy.len(), 1);
+        for probe in probes {
+            assert!(
+                probe.semaphore.map_or(true, |s| s == self.semaphore),
+                "{}",
+                probe
+            );
+            // The probe is somewhere inside `site`, which is tiny.
+            assert!(
+                self.site <= probe.location && probe.location < self.site + 0x1000,
+                "{} outside {:#x}",
+                probe,
+                self.site
+            );
+        }
+    }
+}
+
+/// The child side: report the layout, then wait for the parent to enable the
+/// probe through its semaphore.
+#[test]
+fn child() {
+    if env::var_os(CHILD).is_none() {
+        return;
+    }
+    let layout = Layout::current();
+    layout.check(registry::find("aslr", "site"));
+    println!(
+        "layout {} {:#x} {:#x}",
+        std::process::id(),
+        layout.site,
+        layout.semaphore
+    );
+
+    let start = Instant::now();
+    while !site() {
+        assert!(start.elapsed() < Duration::from_secs(30), "never enabled");
+        thread::sleep(Duration::from_millis(10));
+    }
+    println!("fired");
+}
+
+fn next_line(lines: &mut Lines<BufReader<ChildStdout>>, prefix: &str) -> String {
+    for line in lines {
+        let line = line.unwrap();
+        // libtest may have started the line with the test's name.
+        if let Some((_, rest)) = line.split_once(prefix) {
+            return rest.into();
+        }
+    }
+    panic!("child exited before {:?}", prefix);
+}
+
+fn parse_hex(s: &str) -> u64 {
+    u64::from_str_radix(s.trim_start_matches("0x"), 16).unwrap()
+}
+
+/// Run the child test under `wrapper`, if any, and check that both the
+/// registry and the consumer resolve its probe. Returns the child's layout.
+fn run_child(wrapper: &[&str]) -> Layout {
+    let exe = env::current_exe().unwrap();
+    let mut command = match wrapper.split_first() {
+        Some((program, args)) => {
+            let mut command = Command::new(program);
+            command.args(args).arg(exe);
+            command
+        }
+        None => Command::new(exe),
+    };
+    let mut child = command
+        .args(["--exact", "child", "--nocapture", "--test-threads=1"])
+        .env(CHILD, "1")
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut lines = BufReader::new(child.stdout.take().unwrap()).lines();
+
+    let line = next_line(&mut lines, "layout ");
+    let fields: Vec<_> = line.split(' ').collect();
+    let pid: u32 = fields[0].parse().unwrap();
+    assert_eq!(pid, child.id());
+    let layout = Layout {
+        site: parse_hex(fields[1]),
+        semaphore: parse_hex(fields[2]),
+    };
+
+    let registry = registry::process(pid).unwrap();
+    layout.check(registry.find("aslr", "site"));
+    let enabled = consumer::enable_in(pid, "aslr", "site").unwrap();
+    assert_eq!(enabled.semaphores(), [layout.semaphore]);
+    next_line(&mut lines, "fired");
+    drop(enabled);
+
+    assert!(child.wait().unwrap().success());
+    layout
+}
+
+#[test]
+fn randomized() {
+    // Two runs usually differ with PIE, but they needn't, so just check both.
+    run_child(&[]);
+    run_child(&[]);
+}
+
+#[test]
+fn not_randomized() {
+    // `setarch -R` may be missing, or personality changes may be forbidden.
+    match Command::new("setarch").args(["-R", "true"]).status() {
+        Ok(status) if status.success() => {}
+        _ => return,
+    }
+    let first = run_child(&["setarch", "-R"]);
+    let second = run_child(&["setarch", "-R"]);
+    assert_eq!(first, second, "layout should be fixed without ASLR");
+}
@@ -0,0 +1,223 @@
+#![cfg(feature = "backend")]
+
+use probe::backend::{self, ProbeBackend};
+use probe::{probe, probe_lazy};
+use std::sync::{Mutex, Once};
+
+struct Recorder {
+    events: Mutex<Vec<(&'static str, &'static str, Vec<isize>)>>,
+}
+
+impl ProbeBackend for Recorder {
+    fn enabled(&self, _provider: &'static str, name: &'static str) -> bool {
+        name != "ignored"
+    }
+
+    fn fire(&self, provider: &'static str, name: &'static str, args: &[isize]) {
+        let mut events = self.events.lock().unwrap();
+        events.push((provider, name, args.to_vec()));
+    }
+}
+
+static RECORDER: Recorder = Recorder {
+    events: Mutex::new(Vec::new()),
+};
+
+fn install() {
+    static ONCE: Once = Once::new();
+    ONCE.call_once(|| backend::set_backend(&RECORDER).unwrap());
+}
+
+fn fired(name: &str) -> Vec<Vec<isize>> {
+    let events = RECORDER.events.lock().unwrap();
+    events
+        .iter()
+        .filter(|event| event.0 == "backend" && event.1 == name)
+        .map(|event| event.2.clone())
+        .collect()
+}
+
+#[test]
+fn set_twice() {
+    install();
+    assert!(backend::set_backend(&RECORDER).is_err());
+}
+
+#[test]
+fn eager() {
+    install();
+    probe!(backend, eager);
+    probe!(backend, eager, 1, 2u8, -3i64);
+    assert_eq!(fired("eager"), [vec![], vec![1, 2, -3]]);
+}
+
+#[test]
+fn eager_evaluates_once() {
+    install();
+    let mut count = 0;
+    probe!(backend, once, {
+        count += 1;
+        count
+    });
+    assert_eq!(count, 1);
+    assert_eq!(fired("once"), [vec![1]]);
+}
+
+#[test]
+fn lazy() {
+    install();
+    let mut count = 0;
+    assert!(probe_lazy!(backend, lazy, {
+        count += 1;
+        count
+    }));
+    assert_eq!(count, 1);
+    assert_eq!(fired("lazy"), [vec![1]]);
+
+    assert!(!probe_lazy!(backend, ignored, {
+        count += 1;
+        count
+    }));
+    assert_eq!(count, 1, "disabled lazy probes don't evaluate arguments");
+    assert!(fired("ignored").is_empty());
+}
+
+#[test]
+fn max_args() {
+    install();
+    probe!(backend, max, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12);
+    assert_eq!(fired("max"), [(1..=12).collect::<Vec<_>>()]);
+}
+
+#[test]
+fn wrapped_args() {
+    use std::num::NonZeroU32;
+
+    #[repr(transparent)]
+    struct Fd(i32);
+
+    // Not `Copy`, which a cast doesn't need.
+    enum Level {
+        Warn = 2,
+    }
+
+    install();
+    let id = NonZeroU32::new(7).unwrap();
+    let fd = Fd(-1);
+    let level = Level::Warn;
+    probe!(backend, wrapped, id.get(), fd.0, level);
+    assert_eq!(fired("wrapped"), [vec![7, -1, 2]]);
+}
+
+#[test]
+fn muted_provider() {
+    install();
+    probe::control::set_provider_enabled("backend_muted", false).unwrap();
+    let mut count = 0;
+    probe!(backend_muted, eager, {
+        count += 1;
+        count
+    });
+    assert_eq!(count, 1, "eager arguments are still evaluated");
+    assert!(!probe_lazy!(backend_muted, lazy, {
+        count += 1;
+        count
+    }));
+    assert_eq!(count, 1);
+
+    probe::control::set_provider_enabled("backend_muted", true).unwrap();
+    assert!(probe_lazy!(backend_muted, lazy, 0));
+
+    let events = RECORDER.events.lock().unwrap();
+    let muted: Vec<_> = events
+        .iter()
+        .filter(|event| event.0 == "backend_muted")
+        .map(|event| event.1)
+        .collect();
+    assert_eq!(muted, ["lazy"]);
+}
+
+#[test]
+fn muted_by_name() {
+    use probe::control;
+
+    let long = "x".repeat(control::MAX_LEN + 1);
+    assert!(control::set_provider_enabled(&long, false).is_err());
+    assert!(control::set_provider_enabled(&long, true).is_ok());
+
+    // Only the exact name is muted.
+    let name = String::from("backend_exact");
+    control::set_provider_enabled(&name, false).unwrap();
+    assert!(!control::provider_enabled("backend_exact"));
+    assert!(control::provider_enabled("backend_exac"));
+    assert!(control::provider_enabled("backend_exactly"));
+    control::set_provider_enabled(&name, true).unwrap();
+    assert!(control::provider_enabled("backend_exact"));
+}
+
+#[test]
+fn owned_pattern() {
+    use probe::control;
+
+    // Patterns are copied, so they can be built at runtime.
+    let pattern = format!("backend_{}:*", "owned");
+    control::set_pattern_enabled(&pattern, false).unwrap();
+    drop(pattern);
+    assert!(!control::enabled("backend_owned", "any"));
+    control::set_pattern_enabled("backend_owned:*", true).unwrap();
+    assert!(control::enabled("backend_owned", "any"));
+
+    let long = "x".repeat(control::MAX_LEN + 1);
+    assert!(control::set_pattern_enabled(&long, false).is_err());
+}
+
+#[test]
+fn muted_pattern() {
+    use probe::control;
+
+    install();
+    control::set_pattern_enabled("backend_glob:*", false).unwrap();
+    control::set_pattern_enabled("backend_glob:keep_?", true).unwrap();
+    probe!(backend_glob, dropped);
+    probe!(backend_glob, keep_1);
+    probe!(backend_glob, keep_10);
+    assert!(control::provider_enabled("backend_glob"));
+    assert!(!control::enabled("backend_glob", "dropped"));
+
+    control::set_pattern_enabled("backend_glob:*", true).unwrap();
+    probe!(backend_glob, keep_10);
+
+    let events = RECORDER.events.lock().unwrap();
+    let fired: Vec<_> = events
+        .iter()
+        .filter(|event| event.0 == "backend_glob")
+        .map(|event| event.1)
+        .collect();
+    assert_eq!(fired, ["keep_1", "keep_10"]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn baggage() {
+    install();
+    probe::baggage::scope(99, || {
+        probe!(
+            #[baggage]
+            backend,
+            baggage,
+            1
+        );
+        probe!(
+            #[baggage]
+            backend,
+            baggage
+        );
+        assert!(probe_lazy!(
+            #[baggage]
+            backend,
+            baggage,
+            2
+        ));
+    });
+    assert_eq!(fired("baggage"), [vec![1, 99], vec![99], vec![2, 99]]);
+}
@@ -0,0 +1,50 @@
+#![cfg(feature = "std")]
+
+use probe::{baggage, probe, probe_lazy};
+use std::panic;
+use std::thread;
+
+#[test]
+fn set_and_scope() {
+    assert_eq!(baggage::get(), 0);
+    assert_eq!(baggage::set(1), 0);
+    let inner = baggage::scope(2, || {
+        probe!(
+            #[baggage]
+            baggage,
+            eager,
+            1
+        );
+        probe!(
+            #[baggage]
+            #[ordered]
+            #[semaphore]
+            baggage,
+            ordered
+        );
+        let _ = probe_lazy!(
+            #[baggage]
+            baggage,
+            lazy,
+            1,
+            2
+        );
+        let _ = probe_lazy!(
+            #[baggage]
+            #[export_name = "baggage_exported"]
+            baggage,
+            exported
+        );
+        baggage::get()
+    });
+    assert_eq!(inner, 2);
+    assert_eq!(baggage::get(), 1);
+    assert_eq!(thread::spawn(baggage::get).join().unwrap(), 0);
+}
+
+#[test]
+fn scope_restores_on_panic() {
+    let result = panic::catch_unwind(|| baggage::scope(3, || panic!("oops")));
+    assert!(result.is_err());
+    assert_eq!(baggage::get(), 0);
+}
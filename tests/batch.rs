@@ -0,0 +1,37 @@
+use probe::batch::Batch;
+use probe::probe_batch;
+use std::cell::RefCell;
+
+#[test]
+fn fill_and_clear() {
+    let mut batch = Batch::<3>::new();
+    assert!(!batch.push(1));
+    assert!(!batch.push(2));
+    assert!(batch.push(3));
+    assert!(batch.push(4), "still full");
+    assert_eq!(batch.as_slice(), [1, 2, 3]);
+    batch.clear();
+    assert!(batch.is_empty());
+}
+
+#[test]
+fn probe_batch() {
+    let mut batch = Batch::<4>::new();
+    for i in 0..10u8 {
+        probe_batch!(test, batch, batch, i);
+        assert_eq!(batch.len(), usize::from(i + 1) % 4);
+    }
+    probe_batch!(test, batch, batch);
+    assert!(batch.is_empty());
+}
+
+#[test]
+fn thread_local_batch() {
+    thread_local! {
+        static BATCH: RefCell<Batch<16>> = const { RefCell::new(Batch::new()) };
+    }
+    for i in 0..100 {
+        BATCH.with(|batch| probe_batch!(test, local, batch.borrow_mut(), i));
+    }
+    BATCH.with(|batch| assert_eq!(batch.borrow().len(), 100 % 16));
+}
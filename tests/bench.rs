@@ -0,0 +1,40 @@
+#![cfg(feature = "std")]
+
+use probe::bench;
+
+#[test]
+fn measure() {
+    let baseline = bench::measure(1000);
+    assert_eq!(baseline.iterations, 1000);
+    assert_eq!(baseline.arch, std::env::consts::ARCH);
+    for nanos in [
+        baseline.empty,
+        baseline.nop,
+        baseline.semaphore,
+        baseline.args,
+    ] {
+        assert!(nanos.is_finite() && nanos >= 0.0, "{}", baseline);
+    }
+    assert_eq!(baseline.to_string().lines().count(), 5);
+}
+
+#[test]
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn reserved_probes() {
+    if !probe::is_supported() {
+        return;
+    }
+    let layout = [("nop", 0), ("lazy", 1), ("args", 3)];
+    for (name, args) in layout {
+        let probes = probe::registry::find(bench::PROVIDER, name);
+        assert!(!probes.is_empty(), "rust_bench:{}", name);
+        for probe in probes {
+            let count = probe.arguments.split_whitespace().count();
+            assert_eq!(count, args, "{}", name);
+        }
+    }
+
+    // The backend feature adds an eager site for lazy probes too.
+    let lazy = probe::registry::find(bench::PROVIDER, "lazy");
+    assert!(lazy.iter().any(|probe| probe.semaphore.is_some()));
+}
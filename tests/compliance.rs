@@ -0,0 +1,97 @@
+//! A canonical set of probes, and the contract every platform must satisfy
+//! for them. New platforms should add a way to list their probes to `listed`.
+
+use probe::support;
+use probe::{probe, probe_lazy};
+use std::cell::Cell;
+
+/// Each probe's name, argument count, and whether it's lazy.
+const CANONICAL: &[(&str, usize, bool)] = &[
+    ("empty", 0, false),
+    ("one", 1, false),
+    ("max", 12, false),
+    ("lazy_empty", 0, true),
+    ("lazy_two", 2, true),
+];
+
+/// Fire every canonical probe, counting the arguments that were evaluated.
+///
+/// This is never inlined, so each probe has a single site even when several
+/// tests call it.
+#[inline(never)]
+fn fire(count: &Cell<usize>) {
+    let arg = || {
+        count.set(count.get() + 1);
+        count.get()
+    };
+    probe!(compliance, empty);
+    probe!(compliance, one, arg());
+    probe!(
+        compliance,
+        max,
+        arg(),
+        arg(),
+        arg(),
+        arg(),
+        arg(),
+        arg(),
+        arg(),
+        arg(),
+        arg(),
+        arg(),
+        arg(),
+        arg()
+    );
+    let _ = probe_lazy!(compliance, lazy_empty);
+    let _ = probe_lazy!(compliance, lazy_two, arg(), arg());
+}
+
+/// Returns `(name, argument count, has semaphore)` for each listed site, or
+/// `None` if this platform has no way to list its probes.
+#[allow(unreachable_code)]
+fn listed() -> Option<Vec<(String, usize, bool)>> {
+    #[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+    if support::PLATFORM == support::Platform::SystemTap {
+        let registry = probe::registry::current().ok()?;
+        let probes = registry.probes().iter();
+        let ours = probes.filter(|p| p.provider == "compliance");
+        return Some(
+            ours.map(|p| {
+                let args = p.arguments.split(' ').filter(|a| !a.is_empty()).count();
+                (p.name.clone(), args, p.semaphore.is_some())
+            })
+            .collect(),
+        );
+    }
+    None
+}
+
+#[test]
+fn evaluation() {
+    // Eager probes always evaluate their arguments. Lazy ones only do when
+    // enabled, which nothing has done here.
+    let count = Cell::new(0);
+    fire(&count);
+    assert_eq!(count.get(), 13);
+}
+
+#[test]
+fn listing() {
+    fire(&Cell::new(0));
+    let listed = match listed() {
+        Some(listed) => listed,
+        None => return,
+    };
+    for &(name, args, lazy) in CANONICAL {
+        let sites: Vec<_> = listed.iter().filter(|l| l.0 == name).collect();
+        assert!(!sites.is_empty(), "{} is not listed", name);
+        for site in &sites {
+            assert_eq!(site.1, args, "{} arguments", name);
+        }
+        // Lazy probes have semaphores where the platform supports them. With
+        // the backend feature, they also have an eager site without one.
+        let lazy_sites = sites.iter().filter(|site| site.2).count();
+        let expected = lazy && support::PLATFORM.has_semaphores();
+        assert_eq!(lazy_sites, expected as usize, "{} semaphore", name);
+    }
+}
@@ -0,0 +1,57 @@
+#![cfg(all(
+    feature = "std",
+    any(target_os = "linux", target_os = "android"),
+    not(probe_noop)
+))]
+
+use probe::consumer::enable_in;
+use probe::{probe, probe_lazy, registry};
+
+#[test]
+fn enable_in_self() {
+    let fire = |count: &mut i32| {
+        probe_lazy!(consumer, lazy, {
+            *count += 1;
+            *count
+        })
+    };
+    let mut count = 0;
+    // Make sure the registry includes this probe before enabling it.
+    assert!(!fire(&mut count));
+
+    let enabled = enable_in(std::process::id(), "consumer", "lazy").unwrap();
+    assert!(!enabled.semaphores().is_empty());
+    assert!(fire(&mut count));
+    assert_eq!(count, 1);
+
+    drop(enabled);
+    assert!(!fire(&mut count));
+    assert_eq!(count, 1);
+}
+
+#[test]
+fn eager_has_no_semaphore() {
+    probe!(consumer, eager, 1);
+    match enable_in(std::process::id(), "consumer", "eager") {
+        Err(registry::Error::NoSemaphore(probe)) => assert_eq!(probe, "consumer:eager"),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[inline(always)]
+fn shared() -> bool {
+    probe_lazy!(consumer, shared)
+}
+
+#[test]
+fn shared_semaphore() {
+    // Inlining copies the site, but not its semaphore.
+    let fired = [shared(), shared()];
+    assert_eq!(fired, [false, false]);
+    let sites = registry::find("consumer", "shared");
+    assert!(sites.len() >= 2, "{:?}", sites);
+
+    let enabled = enable_in(std::process::id(), "consumer", "shared").unwrap();
+    assert_eq!(enabled.semaphores().len(), 1);
+    assert!(shared());
+}
@@ -0,0 +1,13 @@
+#![cfg(feature = "epoch")]
+
+use probe::epoch;
+
+#[test]
+fn advance_and_flush() {
+    let mut garbage = vec![1, 2, 3];
+    epoch::advance(1);
+    epoch::advance(2);
+    let freed = epoch::flush(2, garbage.len(), || garbage.drain(..).count());
+    assert_eq!(freed, 3);
+    assert!(garbage.is_empty());
+}
@@ -0,0 +1,110 @@
+use probe::{probe, probe_lazy};
+use std::cell::{Cell, RefCell};
+use std::panic::{self, AssertUnwindSafe};
+
+#[test]
+fn eager_order() {
+    let order = RefCell::new(Vec::new());
+    let arg = |i| {
+        order.borrow_mut().push(i);
+        i
+    };
+    probe!(test, order, arg(1), arg(2), arg(3), arg(4));
+    assert_eq!(*order.borrow(), [1, 2, 3, 4]);
+}
+
+#[test]
+fn eager_once() {
+    let count = Cell::new(0);
+    for _ in 0..10 {
+        probe!(test, once, {
+            count.set(count.get() + 1);
+            count.get()
+        });
+    }
+    assert_eq!(count.get(), 10);
+}
+
+#[test]
+fn eager_short_circuit() {
+    let evaluated = Cell::new(false);
+    let check = |skip: bool| {
+        evaluated.set(true);
+        skip
+    };
+    let skip = std::hint::black_box(false);
+    probe!(test, short_circuit, skip && check(true));
+    assert!(!evaluated.get());
+    probe!(test, short_circuit, !skip && check(true));
+    assert!(evaluated.get());
+}
+
+#[test]
+fn eager_panic() {
+    let first = Cell::new(false);
+    let last = Cell::new(false);
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        probe!(
+            test,
+            panic,
+            {
+                first.set(true);
+                1
+            },
+            {
+                if first.get() {
+                    panic!("argument panicked");
+                }
+                2
+            },
+            {
+                last.set(true);
+                3
+            }
+        );
+    }));
+    assert!(result.is_err());
+    assert!(first.get(), "earlier arguments are evaluated");
+    assert!(!last.get(), "later arguments are not evaluated");
+}
+
+#[test]
+fn lazy_not_evaluated() {
+    let count = Cell::new(0);
+    let enabled = probe_lazy!(test, lazy, {
+        count.set(count.get() + 1);
+        count.get()
+    });
+    assert!(!enabled, "nothing is attached during tests");
+    assert_eq!(count.get(), 0);
+}
+
+#[test]
+fn ordered() {
+    let count = Cell::new(0);
+    let inc = || {
+        count.set(count.get() + 1);
+        count.get()
+    };
+    probe!(
+        #[ordered]
+        test,
+        ordered,
+        inc()
+    );
+    probe!(
+        #[ordered(fence)]
+        test,
+        ordered_fence,
+        inc(),
+        inc()
+    );
+    probe!(
+        #[ordered]
+        #[semaphore]
+        test,
+        ordered_semaphore,
+        inc()
+    );
+    assert_eq!(count.get(), 4);
+}
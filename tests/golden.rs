@@ -0,0 +1,242 @@
+//! Compare the instructions around probe sites against golden patterns.
+//!
+//! A small fixture crate is compiled to assembly for each target that has its
+//! standard library installed, and the instruction mnemonics of each fixture
+//! function are compared against `tests/golden/<target>.txt`. Operands are
+//! left out, so register choices don't matter, but any extra spills, reloads,
+//! or flag saves around a probe show up as extra instructions.
+//!
+//! The patterns depend on the compiler version, so this only runs with
+//! `PROBE_GOLDEN=1`, as the CI job does on stable. After a deliberate change,
+//! or for a new target in `TARGETS`, set `PROBE_BLESS=1` to write the current
+//! output as the new golden files.
+//!
+//! The same fixture is also built to objects with the codegen flags of the
+//! static and kernel-style CI builds, whose notes must match the default
+//! build's. That doesn't depend on the compiler version, so it always runs.
+
+#![cfg(all(feature = "std", not(probe_noop)))]
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The targets with golden files, which are checked when installed.
+const TARGETS: &[&str] = &[
+    "x86_64-unknown-linux-gnu",
+    "i686-unknown-linux-gnu",
+    "aarch64-unknown-linux-gnu",
+];
+
+const FIXTURE: &str = r#"
+#![no_std]
+use probe::{probe, probe_lazy};
+
+#[no_mangle]
+pub fn golden_no_args() {
+    probe!(golden, no_args);
+}
+
+#[no_mangle]
+pub fn golden_args(a: usize, b: usize) {
+    probe!(golden, args, a, b);
+}
+
+#[no_mangle]
+pub fn golden_semaphore(a: usize) {
+    probe!(#[semaphore] golden, semaphore, a);
+}
+
+#[no_mangle]
+pub fn golden_lazy(a: usize) -> bool {
+    probe_lazy!(golden, lazy, a)
+}
+"#;
+
+/// Returns whether the standard library for `target` is installed.
+fn installed(target: &str) -> bool {
+    let output = Command::new("rustc")
+        .args(["--print", "target-libdir", "--target", target])
+        .output();
+    match output {
+        Ok(output) if output.status.success() => {
+            let libdir = String::from_utf8_lossy(&output.stdout);
+            Path::new(libdir.trim()).is_dir()
+        }
+        _ => false,
+    }
+}
+
+/// Reduce assembly to one line per `golden_*` function, listing the
+/// mnemonics of its instructions.
+fn mnemonics(asm: &str) -> String {
+    let mut out = String::new();
+    let mut current = None;
+    for line in asm.lines() {
+        let line = line.trim();
+        if let Some(name) = line.strip_suffix(':') {
+            if name.starts_with("golden_") {
+                out.push_str(name);
+                out.push(':');
+                current = Some(name);
+                continue;
+            }
+        }
+        if current.is_none() {
+            continue;
+        }
+        if line.starts_with(".Lfunc_end") {
+            out.push('\n');
+            current = None;
+            continue;
+        }
+        // Numeric labels from the probe's inline asm share a line with an
+        // instruction or directive.
+        let line = match line.split_once(':') {
+            Some((label, rest)) if label.bytes().all(|b| b.is_ascii_digit()) => rest.trim(),
+            _ => line,
+        };
+        let mnemonic = line.split_whitespace().next().unwrap_or("");
+        let skip = mnemonic.is_empty()
+            || mnemonic.starts_with('.')
+            || mnemonic.starts_with('#')
+            || mnemonic.starts_with("//")
+            || mnemonic.starts_with('@')
+            || mnemonic.ends_with(':');
+        if !skip {
+            out.push(' ');
+            out.push_str(mnemonic);
+        }
+    }
+    out
+}
+
+/// Build the fixture for `target` with extra `flags`, emitting `emit`, and
+/// return the path of the file with extension `ext`.
+fn build(target: &str, dir: &Path, flags: &[&str], emit: &str, ext: &str) -> PathBuf {
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(
+        dir.join("Cargo.toml"),
+        format!(
+            r#"
+[package]
+name = "golden"
+version = "0.0.0"
+edition = "2021"
+
+[dependencies]
+probe = {{ path = {:?} }}
+
+[profile.release]
+panic = "abort"
+
+[workspace]
+"#,
+            manifest_dir,
+        ),
+    )
+    .unwrap();
+    fs::write(dir.join("src/lib.rs"), FIXTURE).unwrap();
+
+    let status = Command::new(env!("CARGO"))
+        .args(["rustc", "--quiet", "--offline", "--release", "--lib"])
+        .args(["--target", target, "--", "--emit", emit])
+        .args(flags)
+        // Codegen flags for the test itself would change the patterns.
+        .env_remove("RUSTFLAGS")
+        .env_remove("CARGO_ENCODED_RUSTFLAGS")
+        .current_dir(dir)
+        .status()
+        .unwrap();
+    assert!(status.success(), "failed to compile for {}", target);
+
+    let deps = dir.join("target").join(target).join("release/deps");
+    for entry in fs::read_dir(deps).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().map_or(false, |e| e == ext) {
+            return path;
+        }
+    }
+    panic!("no {} output for {}", emit, target);
+}
+
+fn compile(target: &str, dir: &Path) -> String {
+    fs::read_to_string(build(target, dir, &[], "asm", "s")).unwrap()
+}
+
+#[test]
+fn golden() {
+    let bless = env::var_os("PROBE_BLESS").is_some();
+    if !bless && env::var_os("PROBE_GOLDEN").is_none() {
+        return;
+    }
+    let base = env::temp_dir().join(format!("probe-golden-{}", std::process::id()));
+    let golden_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+
+    let mut failures = Vec::new();
+    for &target in TARGETS {
+        if !installed(target) {
+            continue;
+        }
+        let actual = mnemonics(&compile(target, &base.join(target)));
+        let path = golden_dir.join(format!("{}.txt", target));
+        if bless {
+            fs::write(&path, &actual).unwrap();
+            continue;
+        }
+        let expected = fs::read_to_string(&path).unwrap();
+        if actual != expected {
+            failures.push(format!(
+                "{}:\nexpected:\n{}actual:\n{}",
+                target, expected, actual
+            ));
+        }
+    }
+    fs::remove_dir_all(&base).ok();
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
+
+/// The codegen flags of the CI job that builds for static and kernel-style
+/// binaries.
+const RELOCATION_FLAGS: &[&[&str]] = &[
+    &["-C", "relocation-model=static"],
+    &[
+        "-C",
+        "relocation-model=static",
+        "-C",
+        "target-feature=+crt-static",
+    ],
+    &["-C", "relocation-model=static", "-C", "code-model=kernel"],
+];
+
+#[test]
+fn relocation_models() {
+    use probe::registry;
+
+    let target = "x86_64-unknown-linux-gnu";
+    if !installed(target) {
+        return;
+    }
+    let base = env::temp_dir().join(format!("probe-relocation-{}", std::process::id()));
+    let notes = |dir: &str, flags: &[&str]| {
+        let object = fs::read(build(target, &base.join(dir), flags, "obj", "o")).unwrap();
+        let abis = registry::abis(&object).unwrap();
+        assert!(!abis.is_empty(), "{:?}", flags);
+        assert!(abis.iter().all(|abi| abi.pointer_width == 8), "{:?}", flags);
+        let mut probes = registry::parse(&object).unwrap();
+        probes.sort_by(|a, b| a.name.cmp(&b.name));
+        probes
+    };
+
+    // The notes only hold absolute address words, so every model must
+    // assemble them, and to the same notes as the default model.
+    let expected = notes("default", &[]);
+    let names: Vec<_> = expected.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["args", "lazy", "no_args", "semaphore"]);
+    for (i, flags) in RELOCATION_FLAGS.iter().enumerate() {
+        assert_eq!(notes(&i.to_string(), flags), expected, "{:?}", flags);
+    }
+    fs::remove_dir_all(&base).ok();
+}
@@ -0,0 +1,36 @@
+#![deny(missing_docs)]
+//! Probe groups declared at the top level of a crate.
+
+/// A type from the enclosing module.
+type Lsn = u64;
+
+probe::probe_group! {
+    /// Request probes.
+    pub requests {
+        fn start(id: u64, len: usize);
+        /// Fired on every retry.
+        fn retry(id: u64, attempt: u8,);
+        fn done();
+        fn flushed(lsn: Lsn);
+    }
+}
+
+probe::probe_group! {
+    empty {}
+}
+
+#[test]
+fn names() {
+    assert_eq!(requests::PROVIDER, "requests");
+    assert_eq!(requests::NAMES, ["start", "retry", "done", "flushed"]);
+    assert_eq!(empty::PROVIDER, "empty");
+    assert!(empty::NAMES.is_empty());
+}
+
+#[test]
+fn fire() {
+    requests::start(1, 10);
+    requests::retry(1, 2);
+    requests::done();
+    requests::flushed(Lsn::MAX);
+}
@@ -0,0 +1,129 @@
+use probe::guard::Scope;
+use probe::probe_scope;
+use std::cell::Cell;
+
+#[test]
+fn scope_outcome() {
+    let outcome = Cell::new(None);
+    {
+        let mut scope = Scope::new(|code| outcome.set(Some(code)));
+        assert_eq!(scope.outcome(), 0);
+        scope.set_outcome(7);
+        assert_eq!(outcome.get(), None);
+    }
+    assert_eq!(outcome.get(), Some(7));
+}
+
+#[test]
+fn probe_scope() {
+    let mut scope = probe_scope!(test, enter, exit, 1, 2);
+    scope.set_outcome(-1);
+    drop(scope);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn timer_fires_once_on_drop() {
+    use probe::guard::Timer;
+
+    let fired = Cell::new(0);
+    {
+        let _timer = Timer::new(|_, _| fired.set(fired.get() + 1));
+        assert_eq!(fired.get(), 0);
+    }
+    assert_eq!(fired.get(), 1);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn timer_outcome() {
+    use probe::guard::Timer;
+
+    let outcome = Cell::new(None);
+    {
+        let mut timer = Timer::new(|_, code| outcome.set(Some(code)));
+        assert_eq!(timer.outcome(), 0);
+        timer.set_outcome(-5);
+    }
+    assert_eq!(outcome.get(), Some(-5));
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn probe_timer() {
+    use probe::probe_timer;
+
+    let mut timer = probe_timer!(test, timer);
+    timer.set_outcome(1);
+    drop(timer);
+}
+
+#[cfg(feature = "std")]
+mod future {
+    use probe::guard::TimedFuture;
+    use probe::probe_future;
+    use std::cell::{Cell, RefCell};
+    use std::future::Future;
+    use std::pin::Pin;
+    use std::sync::Arc;
+    use std::task::{Context, Poll, Wake, Waker};
+    use std::time::Duration;
+
+    struct Noop;
+
+    impl Wake for Noop {
+        fn wake(self: Arc<Self>) {}
+    }
+
+    /// A future that is pending `n` times, sleeping while "suspended".
+    struct Pending(u32);
+
+    impl Future for Pending {
+        type Output = u32;
+
+        fn poll(mut self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<u32> {
+            if self.0 == 0 {
+                Poll::Ready(42)
+            } else {
+                self.0 -= 1;
+                Poll::Pending
+            }
+        }
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        let waker = Waker::from(Arc::new(Noop));
+        let mut cx = Context::from_waker(&waker);
+        let mut future = Box::pin(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn polled_time_excludes_suspension() {
+        let times = Cell::new(None);
+        let steps = RefCell::new(Vec::new());
+        let future = TimedFuture::new(
+            Pending(3),
+            |start, polled| times.set(Some((start.elapsed(), polled))),
+            |resumed| steps.borrow_mut().push(resumed),
+        );
+        assert_eq!(block_on(future), 42);
+
+        let (elapsed, polled) = times.get().unwrap();
+        assert!(elapsed >= Duration::from_millis(30));
+        assert!(polled < elapsed);
+        assert_eq!(*steps.borrow(), [false, true, false, true, false, true]);
+    }
+
+    #[test]
+    fn probe_future() {
+        assert_eq!(block_on(probe_future!(test, future, Pending(1))), 42);
+        let future = probe_future!(test, future, Pending(2), suspend, resume);
+        assert_eq!(block_on(future), 42);
+    }
+}
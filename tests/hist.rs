@@ -0,0 +1,63 @@
+#![cfg(all(
+    feature = "backend",
+    feature = "std",
+    any(target_os = "linux", target_os = "android")
+))]
+
+use probe::backend::ProbeBackend;
+use probe::hist::{self, Histograms};
+use std::sync::Mutex;
+use std::{fs, process};
+
+#[derive(Default)]
+struct Recorder(Mutex<Vec<&'static str>>);
+
+impl ProbeBackend for Recorder {
+    fn fire(&self, _provider: &'static str, name: &'static str, _args: &[isize]) {
+        self.0.lock().unwrap().push(name);
+    }
+}
+
+#[test]
+fn buckets() {
+    assert_eq!(hist::bucket(-1), 0);
+    assert_eq!(hist::bucket(0), 0);
+    assert_eq!(hist::bucket(1), 1);
+    assert_eq!(hist::bucket(3), 2);
+    assert_eq!(hist::bucket(4), 3);
+    assert_eq!(hist::bucket(isize::MAX), hist::BUCKETS - 1);
+}
+
+#[test]
+fn scrape() {
+    let histograms = Histograms::new(Recorder::default()).unwrap();
+    assert_eq!(hist::scrape(process::id()).unwrap(), []);
+
+    histograms.fire("hist", "latency", &[5, 0]);
+    histograms.fire("hist", "latency", &[6]);
+    histograms.fire("hist", "latency", &[0]);
+    histograms.fire("hist", "plain", &[]);
+    for i in 0..hist::SLOTS {
+        let name: &'static str = Box::leak(format!("probe_{}", i).into_boxed_str());
+        histograms.fire("hist", name, &[1]);
+    }
+    assert_eq!(histograms.dropped(), 1, "the last probe finds no slot");
+
+    let fired = histograms.inner().0.lock().unwrap().len();
+    assert_eq!(fired, 4 + hist::SLOTS, "every firing is passed on");
+
+    let scraped = hist::scrape(process::id()).unwrap();
+    assert_eq!(scraped.len(), hist::SLOTS);
+    assert_eq!(scraped[0].provider, "hist");
+    assert_eq!(scraped[0].name, "latency");
+    assert_eq!(scraped[0].count(), 3);
+    assert_eq!(scraped[0].buckets[0], 1);
+    assert_eq!(scraped[0].buckets[3], 2);
+    assert_eq!(scraped[1].name, "probe_0");
+    assert_eq!(scraped[1].buckets[1], 1);
+
+    let path = hist::path(process::id());
+    assert!(hist::parse(&fs::read(&path).unwrap()[..100]).is_err());
+    drop(histograms);
+    assert!(!path.exists());
+}
@@ -0,0 +1,91 @@
+// Probes expanded from other macros, next to user items that share names with
+// the crate's internals.
+
+extern crate probe as instrument;
+
+use std::cell::Cell;
+
+#[allow(unused_macros)]
+macro_rules! stringify {
+    ($($t:tt)*) => {
+        compile_error!("probe macros must not use the caller's `stringify!`")
+    };
+}
+
+#[allow(unused_macros)]
+macro_rules! concat {
+    ($($t:tt)*) => {
+        compile_error!("probe macros must not use the caller's `concat!`")
+    };
+}
+
+static SEMAPHORE: usize = 1;
+static SITE: usize = 2;
+
+macro_rules! probes {
+    ($provider:ident: $($name:ident = $arg:expr),* $(,)?) => {
+        $(instrument::probe!($provider, $name, $arg);)*
+    };
+}
+
+macro_rules! nested {
+    ($($name:ident),*) => {
+        $(nested!(@inner $name);)*
+    };
+    (@inner $name:ident) => {
+        probes!(hygiene: $name = SEMAPHORE + SITE)
+    };
+}
+
+macro_rules! lazy {
+    ($name:ident, $arg:expr) => {
+        instrument::probe_lazy!(hygiene, $name, $arg)
+    };
+}
+
+#[test]
+fn generated() {
+    let count = Cell::new(0);
+    let arg = || {
+        count.set(count.get() + 1);
+        count.get()
+    };
+    probes!(hygiene: first = arg(), second = arg(), third = arg());
+    assert_eq!(count.get(), 3);
+    nested!(one, two, three);
+}
+
+#[test]
+fn user_items() {
+    instrument::probe!(hygiene, items, SEMAPHORE, SITE);
+    instrument::probe!(
+        #[semaphore]
+        hygiene,
+        items_semaphore,
+        SEMAPHORE,
+        SITE
+    );
+    let _ = lazy!(lazy_items, SEMAPHORE + SITE);
+    let _ = instrument::probe_lazy!(
+        #[export_name = "hygiene_exported"]
+        hygiene,
+        exported,
+        SEMAPHORE
+    );
+    let _ = instrument::probe_preview!(hygiene, preview, SEMAPHORE);
+    let _ = instrument::provider_guid!(hygiene);
+}
+
+#[test]
+fn user_locals() {
+    let enabled = 1;
+    let batch = 2;
+    let arg1 = 3;
+    instrument::probe!(hygiene, locals, enabled, batch, arg1);
+    let _ = lazy!(lazy_locals, enabled + batch + arg1);
+
+    let mut values = instrument::batch::Batch::<2>::new();
+    instrument::probe_batch!(hygiene, batched, values, batch);
+    instrument::probe_batch!(hygiene, batched, values, arg1);
+    assert!(values.is_empty());
+}
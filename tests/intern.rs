@@ -0,0 +1,76 @@
+#![cfg(feature = "std")]
+
+use probe::intern::{intern, lookup};
+use std::thread;
+
+#[test]
+fn stable_ids() {
+    let a = intern("intern-a");
+    let b = intern("intern-b");
+    assert_ne!(a, b);
+    assert_eq!(intern("intern-a"), a);
+    assert_eq!(intern(&String::from("intern-b")), b);
+    assert_eq!(lookup(a), Some("intern-a"));
+    assert_eq!(lookup(b), Some("intern-b"));
+    assert_eq!(lookup(u32::MAX), None);
+}
+
+#[test]
+fn concurrent() {
+    let threads: Vec<_> = (0..8)
+        .map(|_| {
+            thread::spawn(|| {
+                (0..50)
+                    .map(|i| intern(&format!("intern-shared-{}", i)))
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+    let ids: Vec<_> = threads.into_iter().map(|t| t.join().unwrap()).collect();
+    assert!(ids.iter().all(|t| *t == ids[0]));
+    for (i, &id) in ids[0].iter().enumerate() {
+        assert_eq!(lookup(id), Some(&*format!("intern-shared-{}", i)));
+    }
+}
+
+#[cfg(feature = "backend")]
+#[test]
+fn label_probes() {
+    use probe::backend::{set_backend, ProbeBackend};
+    use probe::intern::dump;
+    use std::sync::Mutex;
+
+    struct Labels(Mutex<Vec<(u32, String)>>);
+
+    impl ProbeBackend for Labels {
+        fn fire(&self, provider: &'static str, name: &'static str, args: &[isize]) {
+            assert_eq!((provider, name), (probe::intern::PROVIDER, "label"));
+            // SAFETY: the label probe passes a pointer and length of a string
+            // that's never freed.
+            let label = unsafe {
+                let bytes = std::slice::from_raw_parts(args[1] as *const u8, args[2] as usize);
+                std::str::from_utf8(bytes).unwrap()
+            };
+            self.0.lock().unwrap().push((args[0] as u32, label.into()));
+        }
+    }
+
+    static LABELS: Labels = Labels(Mutex::new(Vec::new()));
+    set_backend(&LABELS).unwrap();
+
+    let fired = || -> Vec<(u32, String)> {
+        let labels = LABELS.0.lock().unwrap();
+        labels
+            .iter()
+            .filter(|(_, label)| label.starts_with("intern-probe"))
+            .cloned()
+            .collect()
+    };
+
+    let id = intern("intern-probe");
+    intern("intern-probe");
+    assert_eq!(fired(), [(id, "intern-probe".into())]);
+
+    dump();
+    assert_eq!(fired(), vec![(id, "intern-probe".into()); 2]);
+}
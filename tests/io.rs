@@ -0,0 +1,48 @@
+use probe::io;
+
+#[test]
+fn submit_complete() {
+    for user_data in 0..4 {
+        io::submit(user_data, 22);
+    }
+    for user_data in (0..4).rev() {
+        io::complete(user_data, -11);
+    }
+}
+
+#[test]
+fn syscall_result() {
+    let mut called = false;
+    let result = io::syscall(0, || {
+        called = true;
+        -4
+    });
+    assert!(called);
+    assert_eq!(result, -4);
+}
+
+#[test]
+#[cfg(all(
+    feature = "std",
+    any(target_os = "linux", target_os = "android"),
+    not(probe_noop)
+))]
+fn reserved_providers() {
+    io::submit(1, 2);
+    io::complete(1, 0);
+    io::syscall(0, || 0);
+
+    let layout = [
+        ("submit", 2),
+        ("complete", 2),
+        ("syscall_enter", 1),
+        ("syscall_exit", 2),
+    ];
+    for (name, args) in layout {
+        let probes = probe::registry::find(io::PROVIDER, name);
+        assert!(!probes.is_empty(), "rust_io:{}", name);
+        for probe in probes {
+            assert_eq!(probe.arguments.split(' ').count(), args, "{}", name);
+        }
+    }
+}
@@ -0,0 +1,59 @@
+//! `probe_lazy!` has the same shape on every platform: the same optional
+//! attributes and arguments, and a `bool` result. The CI check job compiles
+//! this for targets with each platform implementation.
+
+use probe::probe_lazy;
+
+// A downstream macro that forwards everything to `probe_lazy!`.
+macro_rules! forward {
+    ($($tt:tt)*) => (probe_lazy!($($tt)*));
+}
+
+fn takes_bool(enabled: bool) -> bool {
+    enabled
+}
+
+#[test]
+fn shapes() {
+    let x = 1u8;
+    let a: bool = probe_lazy!(contract, none);
+    let b: bool = probe_lazy!(contract, one, x);
+    let c: bool = probe_lazy!(contract, trailing, x, -1,);
+    let d: bool = probe_lazy!(
+        #[export_name = "contract_exported"]
+        contract,
+        exported,
+        x
+    );
+    let e: bool = forward!(contract, forwarded, x, 2);
+    let f = takes_bool(forward!(
+        #[export_name = "contract_forwarded"]
+        contract,
+        forwarded_export
+    ));
+    let enabled = [a, b, c, d, e, f];
+
+    // Nothing is attached to the test binary, so no lazy probe runs.
+    assert_eq!(enabled, [false; 6]);
+    if probe_lazy!(contract, condition, x) {
+        unreachable!();
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn baggage_shape() {
+    let a: bool = probe_lazy!(
+        #[baggage]
+        contract,
+        baggage,
+        1
+    );
+    let b: bool = forward!(
+        #[baggage]
+        #[export_name = "contract_baggage"]
+        contract,
+        baggage_export
+    );
+    assert!(!a && !b);
+}
@@ -0,0 +1,46 @@
+#![cfg(all(
+    feature = "std",
+    any(target_os = "linux", target_os = "android"),
+    not(probe_noop)
+))]
+
+use probe::mem::{self, Stats};
+use probe::{consumer, watch};
+use std::process;
+use std::sync::mpsc;
+use std::time::Duration;
+
+#[test]
+fn fragmentation() {
+    let stats = Stats {
+        allocated: 10,
+        active: 16,
+        resident: 32,
+    };
+    assert_eq!(stats.fragmentation(), 6);
+    assert_eq!(Stats::default().fragmentation(), 0);
+}
+
+#[test]
+fn report_while_attached() {
+    let (tx, rx) = mpsc::channel();
+    mem::report(Duration::from_millis(10), move || {
+        let _ = tx.send(());
+        Stats::default()
+    })
+    .unwrap();
+    let timeout = watch::INTERVAL * 20;
+    assert!(
+        rx.recv_timeout(watch::INTERVAL * 4).is_err(),
+        "not before attaching"
+    );
+
+    let enabled = consumer::enable_in(process::id(), mem::PROVIDER, "stats").unwrap();
+    rx.recv_timeout(timeout).unwrap();
+    rx.recv_timeout(timeout).unwrap();
+
+    drop(enabled);
+    std::thread::sleep(watch::INTERVAL * 4);
+    while rx.try_recv().is_ok() {}
+    assert!(rx.recv_timeout(watch::INTERVAL * 4).is_err(), "stopped");
+}
@@ -0,0 +1,25 @@
+#![cfg(all(
+    feature = "std",
+    any(target_os = "linux", target_os = "android"),
+    not(probe_noop)
+))]
+
+use probe::{probe, probe_preview, registry};
+
+#[test]
+fn matches_notes() {
+    let x = 1u8;
+    probe!(preview, args, x, -2, 3usize);
+    let preview = probe_preview!(preview, args, x, -2, 3usize);
+
+    let sites = registry::find("preview", "args");
+    assert!(!sites.is_empty());
+    for site in sites {
+        assert!(preview.matches(&site.arguments), "{}", site.arguments);
+    }
+    assert!(!preview.matches(""));
+
+    let empty = probe_preview!(preview, empty);
+    assert_eq!(empty.args, 0);
+    assert!(empty.matches(""));
+}
@@ -0,0 +1,37 @@
+use probe::provider::Guid;
+use probe::provider_guid;
+
+#[test]
+fn tracelogging_guid() {
+    // The example provider from the TraceLogging documentation.
+    let guid = Guid::from_name("MyCompany.MyComponent");
+    assert_eq!(guid.to_string(), "ce5fa4ea-ab00-5402-8b76-9f76ac858fb5");
+}
+
+#[test]
+fn case_insensitive() {
+    assert_eq!(
+        Guid::from_name("my_provider"),
+        Guid::from_name("MY_PROVIDER")
+    );
+    assert_eq!(provider_guid!(my_provider), Guid::from_name("My_Provider"));
+}
+
+#[test]
+fn const_guid() {
+    const GUID: Guid = provider_guid!(foo);
+    assert_eq!(GUID.data3 >> 12, 5, "name-based GUIDs are version 5");
+    assert_ne!(GUID, provider_guid!(bar));
+}
+
+#[test]
+fn long_and_unicode_names() {
+    // Spans several SHA-1 blocks.
+    let long = "x".repeat(200);
+    let guid = Guid::from_name(&long);
+    assert_eq!(guid.to_string(), "c747eed2-9c17-5501-0eb8-7d2887b0d21c");
+
+    // Needs a UTF-16 surrogate pair.
+    let guid = Guid::from_name("probe\u{1F600}");
+    assert_eq!(guid.to_string(), "830046af-cb71-5315-8c9a-bbfb1a61eb99");
+}
@@ -1,4 +1,4 @@
-#![cfg(any(target_os = "linux", target_os = "android"))]
+#![cfg(all(any(target_os = "linux", target_os = "android"), not(probe_noop)))]
 
 use probe::probe;
 use std::env;
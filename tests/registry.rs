@@ -0,0 +1,498 @@
+//! Round-trip tests for the SDT note parser, using synthetic ELF files, and
+//! checks of the notes in this test binary itself.
+
+#![cfg(feature = "std")]
+
+use probe::registry::{self, Probe, Registry};
+
+/// A tiny xorshift generator, so failures are reproducible from the seed.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    fn below(&mut self, n: u64) -> u64 {
+        self.next() % n
+    }
+
+    /// A short string, or occasionally a long one.
+    fn name(&mut self) -> String {
+        let max = if self.below(4) == 0 { 300 } else { 12 };
+        self.string(max)
+    }
+
+    fn string(&mut self, max: u64) -> String {
+        let len = 1 + self.below(max);
+        (0..len)
+            .map(|_| char::from(b'a' + self.below(26) as u8))
+            .collect()
+    }
+}
+
+struct Writer {
+    is64: bool,
+    big_endian: bool,
+    data: Vec<u8>,
+}
+
+impl Writer {
+    fn u16(&mut self, n: u16) {
+        let bytes = if self.big_endian {
+            n.to_be_bytes()
+        } else {
+            n.to_le_bytes()
+        };
+        self.data.extend_from_slice(&bytes);
+    }
+
+    fn u32(&mut self, n: u32) {
+        let bytes = if self.big_endian {
+            n.to_be_bytes()
+        } else {
+            n.to_le_bytes()
+        };
+        self.data.extend_from_slice(&bytes);
+    }
+
+    fn word(&mut self, n: u64) {
+        if self.is64 {
+            let bytes = if self.big_endian {
+                n.to_be_bytes()
+            } else {
+                n.to_le_bytes()
+            };
+            self.data.extend_from_slice(&bytes);
+        } else {
+            self.u32(n as u32);
+        }
+    }
+
+    fn align(&mut self, align: usize) {
+        while self.data.len() % align != 0 {
+            self.data.push(0);
+        }
+    }
+
+    /// Write one SDT note, the way the `probe!` asm template does.
+    fn note(&mut self, probe: &Probe) {
+        let mut desc = Writer {
+            data: Vec::new(),
+            ..*self
+        };
+        desc.word(probe.location);
+        desc.word(probe.base);
+        desc.word(probe.semaphore.unwrap_or(0));
+        for s in [&probe.provider, &probe.name, &probe.arguments] {
+            desc.data.extend_from_slice(s.as_bytes());
+            desc.data.push(0);
+        }
+
+        self.u32(8);
+        self.u32(desc.data.len() as u32);
+        self.u32(3);
+        self.data.extend_from_slice(b"stapsdt\0");
+        self.data.extend_from_slice(&desc.data);
+        self.align(4);
+    }
+
+    fn section(&mut self, name: u32, kind: u32, addr: u64, offset: u64, size: u64) {
+        self.u32(name);
+        self.u32(kind);
+        self.word(0); // flags
+        self.word(addr);
+        self.word(offset);
+        self.word(size);
+        self.u32(0); // link
+        self.u32(0); // info
+        self.word(1); // addralign
+        self.word(0); // entsize
+    }
+}
+
+/// Build an ELF file with a `.note.stapsdt` section holding `probes`.
+fn elf(is64: bool, big_endian: bool, probes: &[Probe], other_notes: bool) -> Vec<u8> {
+    let mut w = Writer {
+        is64,
+        big_endian,
+        data: Vec::new(),
+    };
+    let ehsize = if is64 { 64 } else { 52 };
+    let shentsize = if is64 { 64 } else { 40 };
+
+    // Section contents come right after the file header.
+    w.data.resize(ehsize, 0);
+    let names = b"\0.shstrtab\0.note.stapsdt\0.probes\0";
+    let names_offset = w.data.len() as u64;
+    w.data.extend_from_slice(names);
+    w.align(4);
+
+    let notes_offset = w.data.len() as u64;
+    for probe in probes {
+        if other_notes {
+            // An unrelated note, which the parser must skip.
+            w.u32(4);
+            w.u32(3);
+            w.u32(1);
+            w.data.extend_from_slice(b"GNU\0abc\0");
+        }
+        w.note(probe);
+    }
+    let notes_size = w.data.len() as u64 - notes_offset;
+
+    w.align(8);
+    let shoff = w.data.len() as u64;
+    w.section(0, 0, 0, 0, 0);
+    w.section(1, 3, 0, names_offset, names.len() as u64);
+    w.section(11, 7, 0, notes_offset, notes_size);
+    w.section(25, 1, 0x1000, 0, 0x100);
+
+    let mut header = Writer {
+        data: Vec::new(),
+        ..w
+    };
+    header.data.extend_from_slice(b"\x7fELF");
+    header.data.push(if is64 { 2 } else { 1 });
+    header.data.push(if big_endian { 2 } else { 1 });
+    header.data.push(1);
+    header.data.resize(16, 0);
+    header.u16(2); // e_type
+    header.u16(0); // e_machine
+    header.u32(1); // e_version
+    header.word(0); // e_entry
+    header.word(0); // e_phoff
+    header.word(shoff);
+    header.u32(0); // e_flags
+    header.u16(ehsize as u16);
+    header.u16(0); // e_phentsize
+    header.u16(0); // e_phnum
+    header.u16(shentsize);
+    header.u16(4); // e_shnum
+    header.u16(1); // e_shstrndx
+    assert_eq!(header.data.len(), ehsize);
+
+    w.data[..ehsize].copy_from_slice(&header.data);
+    w.data
+}
+
+fn random_probes(rng: &mut Rng, is64: bool) -> Vec<Probe> {
+    let mask = if is64 { u64::MAX } else { u64::from(u32::MAX) };
+    let count = rng.below(20);
+    (0..count)
+        .map(|_| {
+            let nargs = rng.below(13);
+            let arguments: Vec<_> = (0..nargs)
+                .map(|i| format!("-{}@%r{}", if is64 { 8 } else { 4 }, i))
+                .collect();
+            Probe {
+                provider: rng.name(),
+                name: rng.name(),
+                arguments: arguments.join(" "),
+                location: rng.next() & mask,
+                base: rng.next() & mask,
+                semaphore: match rng.below(2) {
+                    0 => None,
+                    _ => Some(0x1000 + rng.below(0x100)),
+                },
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn round_trip() {
+    let mut rng = Rng(0x5eed_1234_abcd_9876);
+    for case in 0..500 {
+        let is64 = case % 2 == 0;
+        let big_endian = case % 4 >= 2;
+        let other_notes = case % 8 >= 4;
+        let probes = random_probes(&mut rng, is64);
+        let data = elf(is64, big_endian, &probes, other_notes);
+        let parsed = registry::parse(&data).unwrap();
+        assert_eq!(parsed, probes, "case {}", case);
+    }
+}
+
+#[test]
+fn truncated() {
+    let mut rng = Rng(42);
+    let probes = random_probes(&mut rng, true);
+    let data = elf(true, false, &probes, false);
+    for len in (0..data.len()).step_by(7) {
+        // Any prefix must fail cleanly rather than panic.
+        let _ = registry::parse(&data[..len]);
+    }
+}
+
+/// Overwrite a `width`-byte field of `data` with `value`.
+fn patch(data: &mut [u8], big_endian: bool, offset: usize, width: usize, value: u64) {
+    let bytes = if big_endian {
+        value.to_be_bytes()[8 - width..].to_vec()
+    } else {
+        value.to_le_bytes()[..width].to_vec()
+    };
+    data[offset..offset + width].copy_from_slice(&bytes);
+}
+
+/// Read a `width`-byte field of `data`.
+fn peek(data: &[u8], big_endian: bool, offset: usize, width: usize) -> u64 {
+    let mut bytes = [0; 8];
+    if big_endian {
+        bytes[8 - width..].copy_from_slice(&data[offset..offset + width]);
+        u64::from_be_bytes(bytes)
+    } else {
+        bytes[..width].copy_from_slice(&data[offset..offset + width]);
+        u64::from_le_bytes(bytes)
+    }
+}
+
+#[test]
+fn corrupt_fields() {
+    let probes = [Probe {
+        provider: "foo".into(),
+        name: "bar".into(),
+        arguments: "-8@%rax".into(),
+        location: 0x401234,
+        base: 0,
+        semaphore: Some(0x1000),
+    }];
+    for case in 0..4 {
+        let is64 = case % 2 == 0;
+        let big_endian = case >= 2;
+        let data = elf(is64, big_endian, &probes, false);
+        let word = if is64 { 8 } else { 4 };
+        let (shoff, shnum) = if is64 { (0x28, 0x3C) } else { (0x20, 0x30) };
+        let shentsize = if is64 { 64 } else { 40 };
+        // The notes are the third section.
+        let notes = peek(&data, big_endian, shoff, word) as usize + 2 * shentsize;
+        let notes_offset = peek(&data, big_endian, notes + 8 + 2 * word, word) as usize;
+        let max = if is64 { u64::MAX } else { u32::MAX.into() };
+
+        // Each field, with values that overflow or point past the end.
+        let fields = [
+            (shoff, word),
+            (shnum, 2),
+            (notes + 8 + 2 * word, word),
+            (notes + 8 + 3 * word, word),
+            (notes_offset, 4),
+            (notes_offset + 4, 4),
+        ];
+        for &(offset, width) in &fields {
+            let field_max = if width == word {
+                max
+            } else {
+                (1 << (8 * width)) - 1
+            };
+            for value in [field_max, field_max - 0xff, field_max / 2] {
+                let mut data = data.clone();
+                patch(&mut data, big_endian, offset, width, value);
+                let result = registry::parse(&data);
+                assert!(
+                    matches!(result, Err(registry::Error::Malformed(_))),
+                    "case {}, field at {:#x} = {:#x}: {:?}",
+                    case,
+                    offset,
+                    value,
+                    result
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn registry_index() {
+    let mut rng = Rng(7);
+    let mut probes = random_probes(&mut rng, true);
+    // Duplicate some sites, as inlining would.
+    probes.extend(probes.clone().into_iter().take(3));
+    let registry = Registry::new(probes.clone());
+
+    for probe in &probes {
+        let sites = registry.find(&probe.provider, &probe.name);
+        let expected = probes
+            .iter()
+            .filter(|p| p.provider == probe.provider && p.name == probe.name)
+            .count();
+        assert_eq!(sites.len(), expected);
+        assert!(sites.contains(probe));
+    }
+    assert!(registry.find("missing", "probe").is_empty());
+
+    let counts = registry.site_counts();
+    assert_eq!(counts.iter().map(|c| c.2).sum::<usize>(), probes.len());
+    assert!(counts.windows(2).all(|w| w[0].2 >= w[1].2));
+    for &(provider, name, count) in &counts {
+        assert_eq!(registry.find(provider, name).len(), count);
+    }
+
+    let semaphores: Vec<_> = registry.semaphores().map(|(_, s)| s).collect();
+    assert_eq!(
+        semaphores.len(),
+        probes.iter().filter(|p| p.semaphore.is_some()).count()
+    );
+}
+
+#[test]
+fn manifest_line() {
+    let probe = Probe {
+        provider: "foo".into(),
+        name: "bar".into(),
+        arguments: "-8@%rax".into(),
+        location: 0x401234,
+        base: 0,
+        semaphore: Some(0x404010),
+    };
+    assert_eq!(
+        probe.to_string(),
+        r#"foo:bar location=0x401234 semaphore=0x404010 args="-8@%rax""#
+    );
+    let probe = Probe {
+        semaphore: None,
+        ..probe
+    };
+    assert_eq!(
+        probe.to_string(),
+        r#"foo:bar location=0x401234 args="-8@%rax""#
+    );
+}
+
+#[test]
+fn wasi_sites() {
+    fn custom(module: &mut Vec<u8>, name: &str, contents: &[u8]) {
+        let size = 1 + name.len() + contents.len();
+        // A padded LEB128 size, as `wasm-ld` writes them.
+        module.extend_from_slice(&[0, 0x80 | size as u8, 0x80, 0x80, 0x80, 0]);
+        module.push(name.len() as u8);
+        module.extend_from_slice(name.as_bytes());
+        module.extend_from_slice(contents);
+    }
+
+    let mut module = b"\0asm\x01\0\0\0".to_vec();
+    // An empty type section.
+    module.extend_from_slice(&[1, 1, 0]);
+    custom(&mut module, "probe.sites", b"\x01\x00\x02foo\0bar\0");
+    custom(&mut module, "producers", b"\x01\x00\x02foo\0bar\0");
+    custom(&mut module, "probe.sites", b"\x01\x01\x00foo\0lazy\0");
+
+    let site = |name: &str, arguments, lazy| registry::WasiSite {
+        provider: "foo".into(),
+        name: name.into(),
+        arguments,
+        lazy,
+    };
+    assert_eq!(
+        registry::wasi_sites(&module).unwrap(),
+        [site("bar", 2, false), site("lazy", 0, true)]
+    );
+
+    assert!(registry::wasi_sites(&module[..module.len() - 1]).is_err());
+    assert!(registry::wasi_sites(&module[1..]).is_err());
+}
+
+#[test]
+fn sizes() {
+    let probe = |provider: &str, name: &str, semaphore| Probe {
+        provider: provider.into(),
+        name: name.into(),
+        arguments: "-8@%rax".into(),
+        location: 0x401234,
+        base: 0,
+        semaphore,
+    };
+    let probes = [
+        probe("big", "a", Some(0x1000)),
+        probe("big", "a", Some(0x1002)),
+        probe("big", "b", Some(0x1002)),
+        probe("small", "c", None),
+    ];
+    let data = elf(true, false, &probes, true);
+    let sizes = registry::sizes(&data).unwrap();
+
+    // Each note has a 12-byte header, "stapsdt\0", three words, and the
+    // padded strings.
+    let note = |p: &Probe| {
+        let strings = p.provider.len() + p.name.len() + p.arguments.len() + 3;
+        (12 + 8 + 24 + (strings + 3) / 4 * 4) as u64
+    };
+    let size = |provider: &str, sites, semaphore_bytes| registry::Size {
+        provider: provider.into(),
+        sites,
+        note_bytes: probes
+            .iter()
+            .filter(|p| p.provider == provider)
+            .map(note)
+            .sum(),
+        semaphore_bytes,
+    };
+    assert_eq!(sizes, [size("big", 3, 4), size("small", 1, 0)]);
+}
+
+#[cfg(all(any(target_os = "linux", target_os = "android"), not(probe_noop)))]
+fn fire() {
+    probe::probe!(registry, eager, 1);
+    probe::probe_lazy!(registry, lazy, 2);
+}
+
+#[test]
+#[cfg(all(any(target_os = "linux", target_os = "android"), not(probe_noop)))]
+fn budget() {
+    use registry::{check_budget, Budget};
+
+    fire();
+    let exe = std::env::current_exe().unwrap();
+    let usage = check_budget(&exe, &Budget::default()).unwrap();
+    assert!(usage.sites >= 2);
+    assert!(usage.note_bytes > 0);
+
+    let exact = Budget {
+        max_sites: Some(usage.sites),
+        max_note_bytes: Some(usage.note_bytes),
+    };
+    assert_eq!(check_budget(&exe, &exact).unwrap(), usage);
+
+    let over = [
+        Budget {
+            max_sites: Some(usage.sites - 1),
+            ..exact
+        },
+        Budget {
+            max_note_bytes: Some(usage.note_bytes - 1),
+            ..exact
+        },
+    ];
+    for budget in &over {
+        match check_budget(&exe, budget) {
+            Err(err @ registry::Error::OverBudget(_)) => {
+                let report = err.to_string();
+                assert!(
+                    report.contains(&format!("{} sites", usage.sites)),
+                    "{}",
+                    report
+                );
+                assert!(report.contains("most sites: "), "{}", report);
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+    }
+}
+
+#[test]
+#[cfg(all(any(target_os = "linux", target_os = "android"), not(probe_noop)))]
+fn abi_note() {
+    fire();
+    let data = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+    let abis = registry::abis(&data).unwrap();
+    assert_eq!(
+        abis,
+        [registry::Abi {
+            pointer_width: std::mem::size_of::<usize>() as u32,
+            big_endian: cfg!(target_endian = "big"),
+            arguments: registry::Abi::SIGNED_WORDS,
+        }]
+    );
+}
@@ -0,0 +1,36 @@
+#![cfg(all(any(target_os = "linux", target_os = "android"), not(probe_noop)))]
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+fn notes(target_dir: &Path) -> String {
+    let status = Command::new(env!("CARGO"))
+        .args(["build", "--quiet", "--example", "loop", "--target-dir"])
+        .arg(target_dir)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let output = Command::new("readelf")
+        .arg("-n")
+        .arg(target_dir.join("debug/examples/loop"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn identical_notes() {
+    // Build the same example in two separate target directories, and make sure
+    // the probe notes come out the same, in the same order.
+    let base = env::temp_dir().join(format!("probe-reproducible-{}", std::process::id()));
+    let first = notes(&base.join("a"));
+    let second = notes(&base.join("b"));
+    std::fs::remove_dir_all(&base).unwrap();
+
+    assert_eq!(first.matches("NT_STAPSDT").count(), 3);
+    assert_eq!(first, second);
+}
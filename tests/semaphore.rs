@@ -0,0 +1,60 @@
+use probe::{probe_lazy, Semaphore};
+
+extern "C" {
+    static probe_test_semaphore: u16;
+}
+
+#[test]
+fn exported_semaphore() {
+    let enabled = probe_lazy!(
+        #[export_name = "probe_test_semaphore"]
+        test,
+        exported,
+        42
+    );
+    assert!(!enabled);
+
+    // The same flag is reachable by its symbol name, as foreign code would see it.
+    let value = unsafe { core::ptr::read_volatile(&probe_test_semaphore) };
+    assert_eq!(value, 0);
+}
+
+#[test]
+fn semaphore_counts_consumers() {
+    let semaphore = Semaphore::new();
+    assert_eq!(semaphore.count(), 0);
+    assert!(!semaphore.enabled());
+
+    assert_eq!(semaphore.attach(), 0);
+    assert_eq!(semaphore.attach(), 1);
+    assert_eq!(semaphore.count(), 2);
+    assert!(semaphore.enabled());
+
+    assert_eq!(semaphore.detach(), 2);
+    assert!(
+        semaphore.enabled(),
+        "still enabled while one consumer remains"
+    );
+    assert_eq!(semaphore.detach(), 1);
+    assert!(!semaphore.enabled());
+}
+
+#[test]
+fn concurrent_attach() {
+    // The thread sanitizer job runs this to check that reading the count
+    // doesn't race with in-process consumers updating it.
+    static SEMAPHORE: Semaphore = Semaphore::new();
+    let reader = std::thread::spawn(|| {
+        let mut seen = 0;
+        for _ in 0..1000 {
+            seen = seen.max(SEMAPHORE.count());
+        }
+        seen
+    });
+    for _ in 0..1000 {
+        SEMAPHORE.attach();
+        SEMAPHORE.detach();
+    }
+    assert!(reader.join().unwrap() <= 1);
+    assert_eq!(SEMAPHORE.count(), 0);
+}
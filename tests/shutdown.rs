@@ -0,0 +1,46 @@
+#![cfg(all(
+    feature = "std",
+    any(target_os = "linux", target_os = "android"),
+    not(probe_noop)
+))]
+
+use probe::{consumer, probe_lazy, stats, watch};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{env, fs, process};
+
+// Everything is in one test, since shutdown affects the whole process.
+#[test]
+fn shutdown() {
+    let _ = probe_lazy!(shutdown, attach, 1);
+
+    // Dropping the hook disconnects its channel.
+    let (tx, rx) = mpsc::channel::<()>();
+    watch::on_attach("shutdown", "attach", move || {
+        let _ = &tx;
+    })
+    .unwrap();
+    let path = env::temp_dir().join(format!("probe-shutdown-{}.sock", process::id()));
+    let _ = fs::remove_file(&path);
+    stats::serve(UnixListener::bind(&path).unwrap()).unwrap();
+    // A client that sends nothing, which the server is then waiting on.
+    let _idle = UnixStream::connect(&path).unwrap();
+    thread::sleep(Duration::from_millis(200));
+
+    let start = Instant::now();
+    probe::shutdown();
+    assert!(start.elapsed() <= probe::SHUTDOWN_TIMEOUT + Duration::from_millis(500));
+    assert_eq!(rx.recv(), Err(mpsc::RecvError));
+    // The socket file outlives the listener, but nothing answers it.
+    assert!(UnixStream::connect(&path).is_err());
+    fs::remove_file(&path).unwrap();
+
+    // Hooks work again afterwards.
+    let (tx, rx) = mpsc::channel();
+    watch::on_attach("shutdown", "attach", move || tx.send(()).unwrap()).unwrap();
+    let _enabled = consumer::enable_in(process::id(), "shutdown", "attach").unwrap();
+    rx.recv_timeout(watch::INTERVAL * 20).unwrap();
+    probe::shutdown();
+}
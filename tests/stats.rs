@@ -0,0 +1,104 @@
+#![cfg(all(
+    feature = "std",
+    any(target_os = "linux", target_os = "android"),
+    not(probe_noop)
+))]
+
+use probe::{consumer, probe_lazy, stats};
+use std::io::{Read, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::time::Duration;
+use std::{env, fs, process};
+
+fn fire() -> bool {
+    probe_lazy!(stats, request, 1)
+}
+
+fn stat(name: &str) -> stats::Stat {
+    let stats = stats::snapshot().unwrap();
+    let stat = stats
+        .iter()
+        .find(|s| s.provider == "stats" && s.name == name);
+    stat.cloned().expect("probe in snapshot")
+}
+
+#[test]
+fn consumers() {
+    fire();
+    assert_eq!(stat("request").consumers, 0);
+    assert!(stat("request").sites >= 1);
+
+    let enabled = consumer::enable_in(process::id(), "stats", "request").unwrap();
+    assert_eq!(stat("request").consumers, 1);
+    drop(enabled);
+    assert_eq!(stat("request").consumers, 0);
+}
+
+#[test]
+fn serve_unix() {
+    fire();
+    let path = env::temp_dir().join(format!("probe-stats-{}.sock", process::id()));
+    let _ = fs::remove_file(&path);
+    stats::serve(UnixListener::bind(&path).unwrap()).unwrap();
+
+    let mut stream = UnixStream::connect(&path).unwrap();
+    stream.write_all(b"GET /metrics HTTP/1.0\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert!(response.starts_with("HTTP/1.0 200 OK\r\n"), "{}", response);
+    let (_, body) = response.split_once("\r\n\r\n").unwrap();
+    assert!(body.contains("# TYPE probe_consumers gauge\n"), "{}", body);
+    assert!(
+        body.contains("probe_sites{provider=\"stats\",name=\"request\"} "),
+        "{}",
+        body
+    );
+}
+
+#[test]
+fn idle_client() {
+    let path = env::temp_dir().join(format!("probe-stats-idle-{}.sock", process::id()));
+    let _ = fs::remove_file(&path);
+    stats::serve(UnixListener::bind(&path).unwrap()).unwrap();
+
+    // A client that never sends anything only delays the next one.
+    let _idle = UnixStream::connect(&path).unwrap();
+    let mut stream = UnixStream::connect(&path).unwrap();
+    stream
+        .set_read_timeout(Some(Duration::from_secs(10)))
+        .unwrap();
+    stream.write_all(b"GET / HTTP/1.0\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert!(response.starts_with("HTTP/1.0 200 OK\r\n"), "{}", response);
+}
+
+#[cfg(feature = "backend")]
+#[test]
+fn hits() {
+    use probe::backend::{set_backend, ProbeBackend};
+    use probe::probe;
+
+    struct Discard;
+
+    impl ProbeBackend for Discard {
+        fn fire(&self, _provider: &'static str, _name: &'static str, _args: &[isize]) {}
+    }
+
+    static HITS: stats::Hits<Discard> = stats::Hits::new(Discard);
+    set_backend(&HITS).unwrap();
+
+    for i in 0..3 {
+        probe!(stats, hit, i);
+    }
+    assert_eq!(stat("hit").hits, 3);
+    let body = stats::render().unwrap();
+    assert!(
+        body.contains("probe_hits_total{provider=\"stats\",name=\"hit\"} 3\n"),
+        "{}",
+        body
+    );
+}
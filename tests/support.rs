@@ -0,0 +1,26 @@
+use probe::support::{self, Platform};
+
+#[test]
+fn compiled_platform() {
+    let expected = if cfg!(probe_noop) {
+        Platform::NoOp
+    } else if cfg!(any(target_os = "linux", target_os = "android")) {
+        Platform::SystemTap
+    } else if cfg!(all(target_os = "wasi", feature = "wasi-host")) {
+        Platform::WasiHost
+    } else {
+        Platform::NoOp
+    };
+    assert_eq!(support::PLATFORM, expected);
+    assert_eq!(probe::is_supported(), expected != Platform::NoOp);
+}
+
+#[test]
+fn capabilities() {
+    const SUPPORTED: bool = probe::is_supported();
+    assert_eq!(SUPPORTED, support::PLATFORM.is_supported());
+    assert!(Platform::SystemTap.has_semaphores());
+    assert!(!Platform::WasiHost.has_semaphores());
+    assert!(!Platform::NoOp.has_semaphores());
+    assert!(!Platform::NoOp.is_supported());
+}
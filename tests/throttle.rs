@@ -0,0 +1,55 @@
+#![cfg(all(feature = "backend", feature = "std"))]
+
+use probe::backend::ProbeBackend;
+use probe::throttle::Throttle;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+
+#[derive(Default)]
+struct Recorder(Mutex<Vec<(&'static str, Vec<isize>)>>);
+
+impl ProbeBackend for Recorder {
+    fn fire(&self, _provider: &'static str, name: &'static str, args: &[isize]) {
+        self.0.lock().unwrap().push((name, args.to_vec()));
+    }
+}
+
+#[test]
+fn drops_within_interval() {
+    let throttle = Throttle::new(Recorder::default(), Duration::from_secs(3600));
+    for i in 0..10 {
+        throttle.fire("throttle", "hot", &[i]);
+    }
+    throttle.fire("throttle", "cold", &[]);
+
+    let fired = throttle.inner().0.lock().unwrap().clone();
+    assert_eq!(fired, [("hot", vec![0]), ("cold", vec![])]);
+    assert_eq!(throttle.dropped(), 9);
+    assert_eq!(throttle.dropped_by("throttle", "hot"), 9);
+    assert_eq!(throttle.dropped_by("throttle", "cold"), 0);
+    assert_eq!(throttle.dropped_by("throttle", "missing"), 0);
+}
+
+#[test]
+fn lazy_enabled() {
+    let throttle = Throttle::new(Recorder::default(), Duration::from_secs(3600));
+    assert!(throttle.enabled("throttle", "lazy"));
+    assert!(
+        throttle.enabled("throttle", "lazy"),
+        "only firing starts an interval"
+    );
+    throttle.fire("throttle", "lazy", &[1]);
+    assert!(!throttle.enabled("throttle", "lazy"));
+    assert_eq!(throttle.dropped(), 1);
+}
+
+#[test]
+fn passes_after_interval() {
+    let throttle = Throttle::new(Recorder::default(), Duration::from_millis(1));
+    throttle.fire("throttle", "slow", &[1]);
+    thread::sleep(Duration::from_millis(5));
+    throttle.fire("throttle", "slow", &[2]);
+    assert_eq!(throttle.inner().0.lock().unwrap().len(), 2);
+    assert_eq!(throttle.dropped(), 0);
+}
@@ -0,0 +1,55 @@
+use probe::probe_tiered;
+use std::cell::Cell;
+
+fn fire(basic: &Cell<u32>, verbose: &Cell<u32>) -> bool {
+    probe_tiered!(tiered, basic, verbose, basic.replace(basic.get() + 1); {
+        verbose.set(verbose.get() + 1);
+        verbose.get()
+    })
+}
+
+#[test]
+fn not_enabled() {
+    let (basic, verbose) = (Cell::new(0), Cell::new(0));
+    assert!(!fire(&basic, &verbose));
+    assert_eq!((basic.get(), verbose.get()), (0, 0));
+
+    // Either list of arguments may be empty.
+    assert!(!probe_tiered!(tiered, empty, empty_verbose;));
+    assert!(!probe_tiered!(tiered, no_extra, no_extra_verbose, 1;));
+    assert!(!probe_tiered!(tiered, no_basic, no_basic_verbose; 1, 2,));
+}
+
+#[cfg(all(
+    feature = "std",
+    any(target_os = "linux", target_os = "android"),
+    not(probe_noop)
+))]
+#[test]
+fn tiers() {
+    use probe::consumer::enable_in;
+    use std::process;
+
+    let (basic, verbose) = (Cell::new(0), Cell::new(0));
+    // Make sure the registry includes these probes before enabling them.
+    assert!(!fire(&basic, &verbose));
+
+    // The verbose flag alone doesn't fire the probe.
+    let flag = enable_in(process::id(), "tiered", "verbose").unwrap();
+    assert!(!fire(&basic, &verbose));
+    assert_eq!((basic.get(), verbose.get()), (0, 0));
+    drop(flag);
+
+    let probe = enable_in(process::id(), "tiered", "basic").unwrap();
+    assert!(fire(&basic, &verbose));
+    assert_eq!((basic.get(), verbose.get()), (1, 0));
+
+    let flag = enable_in(process::id(), "tiered", "verbose").unwrap();
+    assert!(fire(&basic, &verbose));
+    assert_eq!((basic.get(), verbose.get()), (2, 1));
+
+    drop(flag);
+    drop(probe);
+    assert!(!fire(&basic, &verbose));
+    assert_eq!((basic.get(), verbose.get()), (2, 1));
+}
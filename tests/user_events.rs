@@ -0,0 +1,42 @@
+#![cfg(all(
+    feature = "backend",
+    feature = "std",
+    any(target_os = "linux", target_os = "android")
+))]
+
+use probe::backend::ProbeBackend;
+use probe::user_events::{Format, UserEvents};
+use std::path::Path;
+
+#[test]
+fn register() {
+    // This needs a kernel with user_events, and usually root.
+    let events = match UserEvents::new() {
+        Ok(events) => events,
+        Err(_) => return,
+    };
+    assert!(!events.enabled("probe_test", "registered"));
+    events.fire("probe_test", "registered", &[1, 2, 3]);
+
+    let tracefs = ["/sys/kernel/tracing", "/sys/kernel/debug/tracing"];
+    let event = "events/user_events/probe_test_registered";
+    assert!(tracefs.iter().any(|t| Path::new(t).join(event).is_dir()));
+
+    // Dropping unregisters the event, so it may be deleted.
+    drop(events);
+}
+
+#[test]
+fn register_event_header() {
+    let events = match UserEvents::with_format(Format::EventHeader) {
+        Ok(events) => events,
+        Err(_) => return,
+    };
+    assert!(!events.enabled("probe_test", "header"));
+    events.fire("probe_test", "header", &[1, 2, 3]);
+
+    // Every probe of the provider shares one tracepoint.
+    let tracefs = ["/sys/kernel/tracing", "/sys/kernel/debug/tracing"];
+    let event = "events/user_events/probe_test_L5K1";
+    assert!(tracefs.iter().any(|t| Path::new(t).join(event).is_dir()));
+}
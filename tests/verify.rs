@@ -0,0 +1,231 @@
+#![cfg(all(
+    feature = "std",
+    any(target_os = "linux", target_os = "android"),
+    not(probe_noop)
+))]
+
+use probe::{probe, probe_lazy, registry};
+use std::env;
+use std::process::Command;
+
+fn fire() {
+    probe!(verify, eager, 1);
+    probe_lazy!(verify, lazy, 2);
+}
+
+fn check(probes: &[registry::Probe]) {
+    let eager = probes
+        .iter()
+        .find(|p| p.provider == "verify" && p.name == "eager");
+    let eager = eager.expect("eager probe note");
+    assert!(eager.semaphore.is_none());
+    assert_ne!(eager.location, 0);
+    if cfg!(feature = "no-base") {
+        assert_eq!(eager.base, 0);
+    }
+
+    // The backend feature adds an eager site for lazy probes too.
+    let mut lazy = probes
+        .iter()
+        .filter(|p| p.provider == "verify" && p.name == "lazy");
+    let lazy = lazy.find(|p| p.semaphore.is_some());
+    let lazy = lazy.expect("lazy probe note with a semaphore");
+    assert_eq!(lazy.arguments.split(' ').count(), 1);
+}
+
+#[test]
+fn verify_current_exe() {
+    fire();
+    let probes = probe::verify_binary(env::current_exe().unwrap()).unwrap();
+    check(&probes);
+}
+
+#[test]
+fn verify_stripped() {
+    fire();
+    let stripped = env::temp_dir().join(format!("probe-verify-{}", std::process::id()));
+    for args in [&["--strip-all"][..], &["--strip-debug"]] {
+        let status = Command::new("strip")
+            .args(args)
+            .arg("-o")
+            .arg(&stripped)
+            .arg(env::current_exe().unwrap())
+            .status()
+            .unwrap();
+        assert!(status.success());
+        let probes = probe::verify_binary(&stripped).unwrap();
+        check(&probes);
+    }
+    std::fs::remove_file(&stripped).unwrap();
+}
+
+#[test]
+fn not_elf() {
+    match registry::parse(b"not an ELF file") {
+        Err(registry::Error::Malformed(_)) => {}
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn find_current() {
+    extern "C" {
+        static registry_find_semaphore: probe::Semaphore;
+    }
+    probe_lazy!(
+        #[export_name = "registry_find_semaphore"]
+        verify,
+        find
+    );
+
+    let sites = registry::find("verify", "find");
+    let semaphore = unsafe { &registry_find_semaphore } as *const _ as u64;
+    assert!(sites.iter().any(|site| site.semaphore == Some(semaphore)));
+    assert!(registry::find("verify", "missing").is_empty());
+
+    let registry = registry::current().unwrap();
+    assert_eq!(registry.find("verify", "find"), sites);
+}
+
+#[test]
+fn eager_semaphore() {
+    let mut count = 0;
+    probe!(
+        #[semaphore]
+        verify,
+        eager_semaphore,
+        {
+            count += 1;
+            count
+        }
+    );
+    assert_eq!(count, 1, "eager arguments are always evaluated");
+
+    let sites = registry::find("verify", "eager_semaphore");
+    assert!(sites.iter().all(|site| site.semaphore.is_some()));
+    assert!(!sites.is_empty());
+}
+
+macro_rules! twice {
+    ($($name:ident),*) => (($(probe!(verify, $name, 1), probe!(verify, $name, 2)),*));
+}
+
+#[test]
+fn one_expression() {
+    let ((), (), (), ()) = twice!(same_line_a, same_line_b);
+    let ((), ()) = (probe!(verify, same_line_c), probe!(verify, same_line_c, 3));
+
+    let registry = registry::current().unwrap();
+    for (name, sites) in [("same_line_a", 2), ("same_line_b", 2), ("same_line_c", 2)] {
+        let probes: Vec<_> = registry
+            .probes()
+            .iter()
+            .filter(|p| p.provider == "verify" && p.name == name)
+            .filter(|p| p.semaphore.is_none())
+            .collect();
+        assert_eq!(probes.len(), sites, "{}", name);
+        assert_ne!(probes[0].location, probes[1].location, "{}", name);
+    }
+}
+
+#[test]
+fn single_nop() {
+    #[inline(never)]
+    fn marker() {
+        probe!(verify, single_nop);
+    }
+    #[inline(never)]
+    fn plain() {}
+    marker();
+    plain();
+
+    // The canonical one-instruction NOP of each architecture.
+    let nop: &[u8] = if cfg!(any(target_arch = "x86_64", target_arch = "x86")) {
+        &[0x90]
+    } else if cfg!(target_arch = "aarch64") {
+        &0xd503201f_u32.to_le_bytes()
+    } else if cfg!(any(target_arch = "riscv64", target_arch = "riscv32")) {
+        // With the C extension, the assembler picks the compressed `c.nop`.
+        if cfg!(target_feature = "c") {
+            &[0x01, 0x00]
+        } else {
+            &0x00000013_u32.to_le_bytes()
+        }
+    } else {
+        return;
+    };
+
+    let probes = registry::find("verify", "single_nop");
+    assert!(!probes.is_empty());
+    for probe in probes {
+        assert_eq!(probe.arguments, "");
+        // SAFETY: the location is the runtime address of the probe's code.
+        let code = unsafe { std::slice::from_raw_parts(probe.location as *const u8, nop.len()) };
+        assert_eq!(code, nop);
+    }
+
+    // Nothing else is added around the NOP, so `marker` is the NOP followed
+    // by the bare return of `plain`, which is as long as a NOP on each of
+    // these architectures. The backend feature also calls into the backend.
+    if !cfg!(feature = "backend") {
+        let (marker, plain) = (marker as fn() as *const u8, plain as fn() as *const u8);
+        assert!(probes.iter().all(|p| p.location == marker as u64));
+        // SAFETY: both functions are at least as long as their return.
+        let (code, ret) = unsafe {
+            (
+                std::slice::from_raw_parts(marker, 2 * nop.len()),
+                std::slice::from_raw_parts(plain, nop.len()),
+            )
+        };
+        assert_eq!(code, [nop, ret].concat());
+    }
+
+    // Disassemble the same site in the file, if objdump is around.
+    let exe = env::current_exe().unwrap();
+    let file = probe::verify_binary(&exe).unwrap();
+    let site = file
+        .iter()
+        .find(|p| p.provider == "verify" && p.name == "single_nop")
+        .unwrap();
+    let output = Command::new("objdump")
+        .arg("-d")
+        .arg(format!("--start-address={:#x}", site.location))
+        .arg(format!(
+            "--stop-address={:#x}",
+            site.location + nop.len() as u64
+        ))
+        .arg(&exe)
+        .output();
+    if let Ok(output) = output {
+        let text = String::from_utf8_lossy(&output.stdout);
+        let insns: Vec<_> = text
+            .lines()
+            .filter(|line| {
+                line.trim_start()
+                    .starts_with(|c: char| c.is_ascii_hexdigit())
+            })
+            .filter(|line| line.contains(":\t"))
+            .collect();
+        assert_eq!(insns.len(), 1, "{}", text);
+        assert!(insns[0].contains("nop"), "{}", text);
+    }
+}
+
+#[test]
+fn version_note() {
+    fire();
+    let data = std::fs::read(env::current_exe().unwrap()).unwrap();
+    let versions = registry::versions(&data).unwrap();
+    assert_eq!(
+        versions,
+        [registry::Version {
+            version: env!("CARGO_PKG_VERSION").into(),
+            format: 1,
+            flags: if cfg!(feature = "no-base") {
+                0
+            } else {
+                registry::Version::HAS_BASE
+            },
+        }]
+    );
+}
@@ -0,0 +1,109 @@
+#![cfg(all(
+    feature = "std",
+    any(target_os = "linux", target_os = "android"),
+    not(probe_noop)
+))]
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+fn copy_dir(from: &Path, to: &Path) {
+    fs::create_dir_all(to).unwrap();
+    for entry in fs::read_dir(from).unwrap() {
+        let entry = entry.unwrap();
+        let name = entry.file_name();
+        if name == "target" || name == ".git" {
+            continue;
+        }
+        if entry.file_type().unwrap().is_dir() {
+            copy_dir(&entry.path(), &to.join(name));
+        } else {
+            fs::copy(entry.path(), to.join(name)).unwrap();
+        }
+    }
+}
+
+const MAIN: &str = r#"
+use probe::registry;
+
+fn main() {
+    probe::probe!(new_version, eager, 1);
+    probe_other::probe_lazy!(old_version, lazy, 2);
+
+    let exe = std::env::current_exe().unwrap();
+    let probes = probe::verify_binary(exe).unwrap();
+    let ours: Vec<_> = probes
+        .iter()
+        .filter(|p| p.provider == "new_version" || p.provider == "old_version")
+        .collect();
+    assert_eq!(ours.len(), 2);
+
+    // Both versions share the one `_.stapsdt.base` symbol.
+    assert!(probes.iter().all(|p| p.base == probes[0].base));
+    assert!(registry::find("old_version", "lazy")[0].semaphore.is_some());
+
+    // Each version has its own version note.
+    let exe = std::fs::read(std::env::current_exe().unwrap()).unwrap();
+    let mut versions: Vec<_> = registry::versions(&exe)
+        .unwrap()
+        .into_iter()
+        .map(|v| v.version)
+        .collect();
+    versions.sort();
+    assert_eq!(versions.len(), 2);
+    assert_eq!(versions[0], "0.0.1");
+}
+"#;
+
+#[test]
+fn two_versions() {
+    // Link this crate with a copy of itself under another version, like a
+    // dependency graph that can't unify them, and check that the notes from
+    // both are intact and agree with each other.
+    let base = env::temp_dir().join(format!("probe-versions-{}", std::process::id()));
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+
+    let other = base.join("other");
+    copy_dir(manifest_dir, &other);
+    let manifest = fs::read_to_string(other.join("Cargo.toml")).unwrap();
+    let version = format!("version = \"{}\"", env!("CARGO_PKG_VERSION"));
+    let manifest = manifest.replacen(&version, "version = \"0.0.1\"", 1);
+    fs::write(other.join("Cargo.toml"), manifest).unwrap();
+    let frontend = fs::read_to_string(other.join("src/frontend.rs")).unwrap();
+    let literal = format!("(\"{}\")", env!("CARGO_PKG_VERSION"));
+    let frontend = frontend.replacen(&literal, "(\"0.0.1\")", 1);
+    fs::write(other.join("src/frontend.rs"), frontend).unwrap();
+
+    let app = base.join("app");
+    fs::create_dir_all(app.join("src")).unwrap();
+    fs::write(
+        app.join("Cargo.toml"),
+        format!(
+            r#"
+[package]
+name = "app"
+version = "0.0.0"
+edition = "2021"
+
+[dependencies]
+probe = {{ path = {:?}, features = ["std"] }}
+probe_other = {{ package = "probe", path = {:?} }}
+
+[workspace]
+"#,
+            manifest_dir, other,
+        ),
+    )
+    .unwrap();
+    fs::write(app.join("src/main.rs"), MAIN).unwrap();
+
+    let status = Command::new(env!("CARGO"))
+        .args(["run", "--quiet", "--offline"])
+        .current_dir(&app)
+        .status()
+        .unwrap();
+    fs::remove_dir_all(&base).unwrap();
+    assert!(status.success());
+}
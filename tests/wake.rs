@@ -0,0 +1,65 @@
+#![cfg(feature = "std")]
+
+use probe::probe_wake;
+use probe::wake::WakeLatency;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::Duration;
+
+struct Count(AtomicUsize);
+
+impl Wake for Count {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// A future that wakes itself `n` times before completing.
+struct Yield(u32);
+
+impl Future for Yield {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 == 0 {
+            Poll::Ready(())
+        } else {
+            self.0 -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+#[test]
+fn wake_latency() {
+    let latencies = std::cell::RefCell::new(Vec::new());
+    let mut future = Box::pin(WakeLatency::new(Yield(2), |woken| {
+        latencies.borrow_mut().push(woken.elapsed())
+    }));
+
+    let count = Arc::new(Count(AtomicUsize::new(0)));
+    let waker = Waker::from(count.clone());
+    let mut cx = Context::from_waker(&waker);
+    while future.as_mut().poll(&mut cx).is_pending() {
+        std::thread::sleep(Duration::from_millis(10));
+    }
+
+    // The executor's waker still sees every wake.
+    assert_eq!(count.0.load(Ordering::Relaxed), 2);
+    drop(future);
+    let latencies = latencies.into_inner();
+    assert_eq!(latencies.len(), 2);
+    assert!(latencies.iter().all(|&l| l >= Duration::from_millis(10)));
+}
+
+#[test]
+fn probe_wake() {
+    let mut future = Box::pin(probe_wake!(test, wake, Yield(1)));
+    let waker = Waker::from(Arc::new(Count(AtomicUsize::new(0))));
+    let mut cx = Context::from_waker(&waker);
+    while future.as_mut().poll(&mut cx).is_pending() {}
+}
@@ -0,0 +1,120 @@
+#![cfg(all(
+    feature = "std",
+    any(target_os = "linux", target_os = "android"),
+    not(probe_noop)
+))]
+
+use probe::{consumer, probe_lazy, watch};
+use std::process;
+use std::sync::mpsc;
+
+#[test]
+fn attach_hook() {
+    let _ = probe_lazy!(watch, attach, 1);
+
+    let (tx, rx) = mpsc::channel();
+    watch::on_attach("watch", "attach", move || tx.send(()).unwrap()).unwrap();
+    let timeout = watch::INTERVAL * 20;
+    assert!(rx.recv_timeout(watch::INTERVAL * 4).is_err());
+
+    let enabled = consumer::enable_in(process::id(), "watch", "attach").unwrap();
+    rx.recv_timeout(timeout).unwrap();
+    assert!(
+        rx.recv_timeout(watch::INTERVAL * 4).is_err(),
+        "only once per attach"
+    );
+
+    drop(enabled);
+    std::thread::sleep(watch::INTERVAL * 4);
+    let _enabled = consumer::enable_in(process::id(), "watch", "attach").unwrap();
+    rx.recv_timeout(timeout).unwrap();
+}
+
+#[test]
+fn eager_only() {
+    probe::probe!(watch, eager);
+    match watch::on_attach("watch", "eager", || {}) {
+        Err(watch::WatchError::NoSemaphore(probe)) => assert_eq!(probe, "watch:eager"),
+        other => panic!("unexpected result: {:?}", other),
+    }
+}
+
+#[test]
+fn detach_hook() {
+    let _ = probe_lazy!(watch, detach, 1);
+
+    let (tx, rx) = mpsc::channel();
+    watch::on_detach("watch", "detach", move || tx.send(()).unwrap()).unwrap();
+    let timeout = watch::INTERVAL * 20;
+
+    let enabled = consumer::enable_in(process::id(), "watch", "detach").unwrap();
+    std::thread::sleep(watch::INTERVAL * 4);
+    assert!(rx.try_recv().is_err(), "not on attach");
+
+    // Only the last of several tools detaching counts.
+    let second = consumer::enable_in(process::id(), "watch", "detach").unwrap();
+    drop(enabled);
+    assert!(rx.recv_timeout(watch::INTERVAL * 4).is_err());
+    drop(second);
+    rx.recv_timeout(timeout).unwrap();
+}
+
+#[test]
+fn panicking_hook() {
+    let _ = probe_lazy!(watch, panics, 1);
+    let _ = probe_lazy!(watch, after_panic, 1);
+
+    watch::on_attach("watch", "panics", || panic!("hook panicked")).unwrap();
+    let _enabled = consumer::enable_in(process::id(), "watch", "panics").unwrap();
+    std::thread::sleep(watch::INTERVAL * 4);
+
+    // The watcher survives, and runs hooks added afterwards.
+    let (tx, rx) = mpsc::channel();
+    watch::on_attach("watch", "after_panic", move || tx.send(()).unwrap()).unwrap();
+    let _enabled = consumer::enable_in(process::id(), "watch", "after_panic").unwrap();
+    rx.recv_timeout(watch::INTERVAL * 20).unwrap();
+}
+
+#[test]
+fn change_hook() {
+    let _ = probe_lazy!(watch, change, 1);
+
+    let (tx, rx) = mpsc::channel();
+    watch::on_change("watch", "change", move |attached| {
+        tx.send(attached).unwrap()
+    })
+    .unwrap();
+    let timeout = watch::INTERVAL * 20;
+    assert!(rx.recv_timeout(watch::INTERVAL * 4).is_err());
+
+    let enabled = consumer::enable_in(process::id(), "watch", "change").unwrap();
+    assert_eq!(rx.recv_timeout(timeout), Ok(true));
+    drop(enabled);
+    assert_eq!(rx.recv_timeout(timeout), Ok(false));
+}
+
+#[test]
+fn tracked_bits() {
+    let _ = probe_lazy!(watch, bit_a, 1);
+    let _ = probe_lazy!(watch, bit_b, 1);
+
+    let a = watch::track("watch", "bit_a").unwrap();
+    let b = watch::track("watch", "bit_b").unwrap();
+    assert_ne!(a, b);
+    assert_eq!(watch::track("watch", "bit_a").unwrap(), a);
+    assert_eq!(watch::attached() & (a | b), 0);
+
+    let wait = |want: usize| {
+        for _ in 0..20 {
+            if watch::attached() & (a | b) == want {
+                return;
+            }
+            std::thread::sleep(watch::INTERVAL);
+        }
+        panic!("bits {:#x} never became {:#x}", watch::attached(), want);
+    };
+    let enabled = consumer::enable_in(process::id(), "watch", "bit_b").unwrap();
+    wait(b);
+    drop(enabled);
+    wait(0);
+}